@@ -0,0 +1,580 @@
+pub mod superbubble;
+
+use bstr::{BString, ByteSlice};
+use fnv::{FnvHashMap, FnvHashSet};
+use rayon::prelude::*;
+
+use crate::{
+    error::{Error, Result},
+    variants::{self, PathData, PathIndices},
+};
+
+#[allow(unused_imports)]
+use log::{debug, info, trace, warn};
+
+/// Map from each bubble to the bubbles it contains.
+pub type BubbleContainment = FnvHashMap<(u64, u64), Vec<(u64, u64)>>;
+
+/// Map from each bubble to its parent bubble, if any, and its
+/// nesting depth.
+pub type BubbleNesting = FnvHashMap<(u64, u64), (Option<(u64, u64)>, usize)>;
+
+/// Properties of an ultrabubble, derived from the bubble nesting and
+/// the paths that traverse the bubble.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BubbleInfo {
+    pub parent: Option<(u64, u64)>,
+    pub depth: usize,
+    /// Number of distinct nodes strictly inside the bubble that are
+    /// traversed by at least one path.
+    pub internal_nodes: usize,
+    pub min_allele_len: usize,
+    pub max_allele_len: usize,
+    /// True if every path traverses the bubble using a single
+    /// internal node of length 1, and there are at least two such
+    /// alleles.
+    pub is_snp: bool,
+    pub path_count: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bubble {
+    pub from: u64,
+    pub to: u64,
+    pub info: Option<BubbleInfo>,
+}
+
+impl Bubble {
+    pub fn new(from: u64, to: u64) -> Self {
+        Self {
+            from,
+            to,
+            info: None,
+        }
+    }
+
+    pub fn ends(&self) -> (u64, u64) {
+        (self.from, self.to)
+    }
+}
+
+/// Find the parent and nesting depth of each bubble, given a map from
+/// each bubble to the bubbles it contains. If a bubble is listed as
+/// contained by several bubbles, the innermost one is used as its
+/// parent.
+pub fn bubble_nesting(contained: &BubbleContainment) -> BubbleNesting {
+    let mut parents: FnvHashMap<(u64, u64), (u64, u64)> = FnvHashMap::default();
+
+    for (&bubble, children) in contained.iter() {
+        for &child in children.iter() {
+            if child == bubble {
+                continue;
+            }
+            let entry = parents.entry(child).or_insert(bubble);
+            let cur_size = contained.get(entry).map_or(0, |c| c.len());
+            if children.len() < cur_size {
+                *entry = bubble;
+            }
+        }
+    }
+
+    contained
+        .keys()
+        .map(|&bubble| {
+            let parent = parents.get(&bubble).copied();
+            let mut depth = 0;
+            let mut cur = parent;
+            while let Some(p) = cur {
+                depth += 1;
+                if depth > contained.len() {
+                    warn!("Cycle in ultrabubble nesting at {:?}", bubble);
+                    break;
+                }
+                cur = parents.get(&p).copied();
+            }
+            (bubble, (parent, depth))
+        })
+        .collect()
+}
+
+/// Compute the path-derived properties of a bubble: the number of
+/// internal nodes, the allele lengths, whether it's a SNP bubble, and
+/// the number of paths traversing it. The nesting fields are left
+/// empty.
+pub fn bubble_path_info(
+    path_data: &PathData,
+    path_indices: &PathIndices,
+    from: u64,
+    to: u64,
+) -> BubbleInfo {
+    let sub_paths =
+        variants::path_data_sub_paths(path_data, path_indices, from, to)
+            .unwrap_or_default();
+
+    let mut internal: FnvHashSet<usize> = FnvHashSet::default();
+    let mut alleles: FnvHashSet<BString> = FnvHashSet::default();
+    let mut min_allele_len = None;
+    let mut max_allele_len = 0;
+    let mut is_snp = !sub_paths.is_empty();
+
+    for (_, sub_path) in sub_paths.iter() {
+        let inner = &sub_path[1..sub_path.len() - 1];
+        let allele_len: usize = inner
+            .iter()
+            .map(|(step, _, _)| {
+                internal.insert(*step);
+                path_data.segment_map.get(step).map_or(0, |s| s.len())
+            })
+            .sum();
+
+        min_allele_len = Some(
+            min_allele_len.map_or(allele_len, |m: usize| m.min(allele_len)),
+        );
+        max_allele_len = max_allele_len.max(allele_len);
+
+        if inner.len() == 1 && allele_len == 1 {
+            let (step, _, _) = inner[0];
+            if let Some(seq) = path_data.segment_map.get(&step) {
+                alleles.insert(seq.as_bstr().to_owned());
+            }
+        } else {
+            is_snp = false;
+        }
+    }
+
+    BubbleInfo {
+        parent: None,
+        depth: 0,
+        internal_nodes: internal.len(),
+        min_allele_len: min_allele_len.unwrap_or(0),
+        max_allele_len,
+        is_snp: is_snp && alleles.len() > 1,
+        path_count: sub_paths.len(),
+    }
+}
+
+/// Annotate each bubble in the containment map with its nesting and
+/// path-derived properties. The result is sorted by bubble ends.
+pub fn annotate_bubbles(
+    path_data: &PathData,
+    path_indices: &PathIndices,
+    contained: &BubbleContainment,
+) -> Vec<Bubble> {
+    let nesting = bubble_nesting(contained);
+
+    let mut bubbles: Vec<Bubble> = nesting
+        .into_par_iter()
+        .map(|((from, to), (parent, depth))| {
+            let info = BubbleInfo {
+                parent,
+                depth,
+                ..bubble_path_info(path_data, path_indices, from, to)
+            };
+            Bubble {
+                from,
+                to,
+                info: Some(info),
+            }
+        })
+        .collect();
+
+    bubbles.sort_by_key(|b| b.ends());
+    bubbles
+}
+
+/// Compute the path-derived properties of the bubbles that don't
+/// already have them. Nesting is unknown for these bubbles, so they
+/// are treated as top-level bubbles.
+pub fn fill_path_info(
+    path_data: &PathData,
+    path_indices: &PathIndices,
+    bubbles: &mut [Bubble],
+) {
+    bubbles
+        .par_iter_mut()
+        .filter(|b| b.info.is_none())
+        .for_each(|b| {
+            b.info =
+                Some(bubble_path_info(path_data, path_indices, b.from, b.to));
+        });
+}
+
+/// Collect the start and end nodes of the given bubbles.
+pub fn bubble_node_set<I>(bubbles: I) -> FnvHashSet<u64>
+where
+    I: IntoIterator<Item = (u64, u64)>,
+{
+    bubbles
+        .into_iter()
+        .flat_map(|(a, b)| {
+            use std::iter::once;
+            once(a).chain(once(b))
+        })
+        .collect()
+}
+
+/// Return the 0-based, half-open interval covered by the bubble on
+/// the given path, including the bubble's start and end nodes.
+pub fn bubble_path_span(
+    path_data: &PathData,
+    path_indices: &PathIndices,
+    path_ix: usize,
+    from: u64,
+    to: u64,
+) -> Option<(usize, usize)> {
+    let from_ix = *path_indices.get(&from)?.get(&path_ix)?;
+    let to_ix = *path_indices.get(&to)?.get(&path_ix)?;
+    let path = path_data.paths.get(path_ix)?;
+
+    let (first, last) = (from_ix.min(to_ix), from_ix.max(to_ix));
+    let start = path[first].1 - 1;
+
+    let (last_step, last_offset, _) = path[last];
+    let last_len = path_data.segment_map.get(&last_step).map_or(0, |s| s.len());
+
+    Some((start, last_offset - 1 + last_len))
+}
+
+/// Split a bubble into smaller bubbles, using the nodes that every
+/// path traversing the bubble visits as boundaries. Returns the
+/// consecutive pairs of such nodes that have at least one node
+/// between them, in the order they're visited by the first path.
+pub fn split_bubble(
+    path_data: &PathData,
+    path_indices: &PathIndices,
+    from: u64,
+    to: u64,
+) -> Vec<(u64, u64)> {
+    let sub_paths = match variants::path_data_sub_paths(
+        path_data,
+        path_indices,
+        from,
+        to,
+    ) {
+        Some(sub_paths) if !sub_paths.is_empty() => sub_paths,
+        _ => return Vec::new(),
+    };
+
+    let mut shared: FnvHashSet<usize> =
+        sub_paths[0].1.iter().map(|&(step, _, _)| step).collect();
+
+    for (_, sub_path) in sub_paths.iter().skip(1) {
+        let nodes: FnvHashSet<usize> =
+            sub_path.iter().map(|&(step, _, _)| step).collect();
+        shared.retain(|n| nodes.contains(n));
+    }
+
+    let anchors: Vec<(usize, usize)> = sub_paths[0]
+        .1
+        .iter()
+        .enumerate()
+        .filter(|(_, (step, _, _))| shared.contains(step))
+        .map(|(ix, &(step, _, _))| (ix, step))
+        .collect();
+
+    anchors
+        .windows(2)
+        .filter_map(|w| {
+            let (a_ix, a) = w[0];
+            let (b_ix, b) = w[1];
+            if b_ix - a_ix > 1 && a != b {
+                Some((a as u64, b as u64))
+            } else {
+                None
+            }
+        })
+        .filter(|&pair| pair != (from, to))
+        .collect()
+}
+
+/// A bubble traversal projected onto a path, as a 0-based, half-open
+/// interval spanning the bubble's start and end nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BubbleInterval {
+    pub path_ix: usize,
+    pub start: usize,
+    pub end: usize,
+    pub from: u64,
+    pub to: u64,
+    /// True if the path visits the end node before the start node.
+    pub reverse: bool,
+}
+
+/// Project the bubbles onto the given paths, using the step offsets
+/// in `PathData`. Each traversal of a bubble by a path produces one
+/// interval. Returns the intervals, sorted by path and position,
+/// along with the bubbles that aren't traversed by any of the paths.
+pub fn project_bubbles(
+    path_data: &PathData,
+    path_ixs: &[usize],
+    bubbles: &[(u64, u64)],
+) -> (Vec<BubbleInterval>, Vec<(u64, u64)>) {
+    let nodes = bubble_node_set(bubbles.iter().copied());
+
+    let occurrences: Vec<(usize, FnvHashMap<u64, Vec<usize>>)> = path_ixs
+        .par_iter()
+        .filter_map(|&path_ix| {
+            let path = path_data.paths.get(path_ix)?;
+            let mut node_steps: FnvHashMap<u64, Vec<usize>> =
+                FnvHashMap::default();
+            for (ix, &(step, _, _)) in path.iter().enumerate() {
+                let step = step as u64;
+                if nodes.contains(&step) {
+                    node_steps.entry(step).or_default().push(ix);
+                }
+            }
+            Some((path_ix, node_steps))
+        })
+        .collect();
+
+    let step_end = |path_ix: usize, ix: usize| {
+        let (step, offset, _) = path_data.paths[path_ix][ix];
+        let len = path_data.segment_map.get(&step).map_or(0, |s| s.len());
+        offset - 1 + len
+    };
+
+    let projected: Vec<((u64, u64), Vec<BubbleInterval>)> = bubbles
+        .par_iter()
+        .map(|&(from, to)| {
+            let mut intervals = Vec::new();
+            for (path_ix, node_steps) in occurrences.iter() {
+                let path_ix = *path_ix;
+                let (from_ixs, to_ixs) =
+                    match (node_steps.get(&from), node_steps.get(&to)) {
+                        (Some(f), Some(t)) => (f, t),
+                        _ => continue,
+                    };
+
+                // Pair each visit of one end of the bubble with the
+                // next visit of the other end
+                let mut visits: Vec<(usize, bool)> = from_ixs
+                    .iter()
+                    .map(|&ix| (ix, true))
+                    .chain(to_ixs.iter().map(|&ix| (ix, false)))
+                    .collect();
+                visits.sort();
+
+                let mut open: Option<(usize, bool)> = None;
+                for (ix, is_from) in visits {
+                    match open {
+                        Some((open_ix, open_from)) if open_from != is_from => {
+                            intervals.push(BubbleInterval {
+                                path_ix,
+                                start: path_data.paths[path_ix][open_ix].1 - 1,
+                                end: step_end(path_ix, ix),
+                                from,
+                                to,
+                                reverse: !open_from,
+                            });
+                            open = None;
+                        }
+                        _ => open = Some((ix, is_from)),
+                    }
+                }
+            }
+            ((from, to), intervals)
+        })
+        .collect();
+
+    let mut all_intervals = Vec::new();
+    let mut unplaced = Vec::new();
+
+    for (bubble, intervals) in projected {
+        if intervals.is_empty() {
+            unplaced.push(bubble);
+        } else {
+            all_intervals.extend(intervals);
+        }
+    }
+
+    all_intervals.sort();
+
+    (all_intervals, unplaced)
+}
+
+/// Header line for the tab-separated bubble format.
+pub static BUBBLE_TSV_HEADER: &str =
+    "#from\tto\tparent_from\tparent_to\tdepth\tnodes\tmin_len\tmax_len\tsnp\tpaths";
+
+fn opt_field<T: std::fmt::Display>(field: Option<T>) -> String {
+    if let Some(x) = field {
+        x.to_string()
+    } else {
+        ".".to_string()
+    }
+}
+
+pub fn bubble_tsv_line(bubble: &Bubble) -> String {
+    if let Some(info) = bubble.info {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            bubble.from,
+            bubble.to,
+            opt_field(info.parent.map(|p| p.0)),
+            opt_field(info.parent.map(|p| p.1)),
+            info.depth,
+            info.internal_nodes,
+            info.min_allele_len,
+            info.max_allele_len,
+            info.is_snp,
+            info.path_count
+        )
+    } else {
+        format!("{}\t{}", bubble.from, bubble.to)
+    }
+}
+
+pub fn bubble_json(bubble: &Bubble) -> String {
+    let mut json = format!("{{\"from\":{},\"to\":{}", bubble.from, bubble.to);
+    if let Some(info) = bubble.info {
+        let parent = info
+            .parent
+            .map(|(a, b)| format!("[{},{}]", a, b))
+            .unwrap_or_else(|| "null".to_string());
+        json.push_str(&format!(
+            ",\"parent\":{},\"depth\":{},\"nodes\":{},\"min_len\":{},\"max_len\":{},\"snp\":{},\"paths\":{}",
+            parent,
+            info.depth,
+            info.internal_nodes,
+            info.min_allele_len,
+            info.max_allele_len,
+            info.is_snp,
+            info.path_count
+        ));
+    }
+    json.push('}');
+    json
+}
+
+static LINE_ERROR: &str = "Ultrabubble record was missing fields";
+
+fn parse_opt_u64(field: &[u8]) -> Result<Option<u64>> {
+    if field == b"." {
+        Ok(None)
+    } else {
+        Ok(Some(field.to_str()?.parse::<u64>()?))
+    }
+}
+
+/// Parse a line in the bubble TSV format. Lines with only the two
+/// bubble ends are also accepted, in which case the bubble has no
+/// `BubbleInfo`.
+pub fn parse_bubble_line(line: &[u8]) -> Result<Bubble> {
+    let fields: Vec<&[u8]> = line.split_str("\t").collect();
+    if fields.len() < 2 {
+        return Err(Error::Parse(LINE_ERROR.to_string()));
+    }
+
+    let from = fields[0].to_str()?.parse::<u64>()?;
+    let to = fields[1].to_str()?.parse::<u64>()?;
+
+    if fields.len() < 10 {
+        return Ok(Bubble::new(from, to));
+    }
+
+    let parent = match (parse_opt_u64(fields[2])?, parse_opt_u64(fields[3])?) {
+        (Some(a), Some(b)) => Some((a, b)),
+        _ => None,
+    };
+
+    let info = BubbleInfo {
+        parent,
+        depth: fields[4].to_str()?.parse()?,
+        internal_nodes: fields[5].to_str()?.parse()?,
+        min_allele_len: fields[6].to_str()?.parse()?,
+        max_allele_len: fields[7].to_str()?.parse()?,
+        is_snp: fields[8].to_str()?.parse()?,
+        path_count: fields[9].to_str()?.parse()?,
+    };
+
+    Ok(Bubble {
+        from,
+        to,
+        info: Some(info),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gfa::gfa::Orientation;
+
+    #[test]
+    fn bubble_tsv_round_trip() {
+        let bubble = Bubble {
+            from: 3,
+            to: 8,
+            info: Some(BubbleInfo {
+                parent: Some((1, 10)),
+                depth: 1,
+                internal_nodes: 2,
+                min_allele_len: 1,
+                max_allele_len: 1,
+                is_snp: true,
+                path_count: 4,
+            }),
+        };
+        let line = bubble_tsv_line(&bubble);
+        assert_eq!(line, "3\t8\t1\t10\t1\t2\t1\t1\ttrue\t4");
+        assert_eq!(parse_bubble_line(line.as_bytes()).unwrap(), bubble);
+
+        let plain = parse_bubble_line(b"5\t9").unwrap();
+        assert_eq!(plain, Bubble::new(5, 9));
+    }
+
+    #[test]
+    fn nesting_uses_innermost_parent() {
+        let mut contained = FnvHashMap::default();
+        contained.insert((1, 20), vec![(2, 10), (3, 5)]);
+        contained.insert((2, 10), vec![(3, 5)]);
+        contained.insert((3, 5), vec![]);
+
+        let nesting = bubble_nesting(&contained);
+        assert_eq!(nesting[&(1, 20)], (None, 0));
+        assert_eq!(nesting[&(2, 10)], (Some((1, 20)), 1));
+        assert_eq!(nesting[&(3, 5)], (Some((2, 10)), 2));
+    }
+
+    #[test]
+    fn project_bubbles_onto_paths() {
+        use Orientation::*;
+        let segments: Vec<(usize, &str)> =
+            (1..=6).map(|id| (id, "AC")).collect();
+        let path_data = variants::path_data_from_steps(
+            &segments,
+            &[
+                (
+                    "fwd",
+                    &[(1, Forward), (2, Forward), (3, Forward), (4, Forward)],
+                ),
+                ("rev", &[(4, Backward), (5, Backward), (2, Backward)]),
+            ],
+        );
+
+        let (intervals, unplaced) =
+            project_bubbles(&path_data, &[0, 1], &[(2, 4), (5, 6)]);
+
+        assert_eq!(unplaced, vec![(5, 6)]);
+        assert_eq!(
+            intervals,
+            vec![
+                BubbleInterval {
+                    path_ix: 0,
+                    start: 2,
+                    end: 8,
+                    from: 2,
+                    to: 4,
+                    reverse: false,
+                },
+                BubbleInterval {
+                    path_ix: 1,
+                    start: 0,
+                    end: 6,
+                    from: 2,
+                    to: 4,
+                    reverse: true,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use fnv::{FnvHashMap, FnvHashSet};
+use rayon::prelude::*;
+
+use gfa::{
+    gfa::{Orientation, GFA},
+    optfields::OptFields,
+};
+
+use crate::util::progress_bar;
+use indicatif::ParallelProgressIterator;
+
+use super::BubbleContainment;
+
+#[allow(unused_imports)]
+use log::{debug, info, trace, warn};
+
+/// An oriented segment, packed as the segment ID shifted left by one,
+/// with the lowest bit set if the segment is reversed.
+type Handle = u64;
+
+fn handle(id: usize, orient: Orientation) -> Handle {
+    let rev = if bool::from(orient) { 0 } else { 1 };
+    ((id as u64) << 1) | rev
+}
+
+fn flip(h: Handle) -> Handle {
+    h ^ 1
+}
+
+fn handle_id(h: Handle) -> u64 {
+    h >> 1
+}
+
+/// Directed view of the bidirected graph, where every segment is
+/// represented by a handle in each orientation.
+#[derive(Debug, Default, Clone)]
+pub struct DirectedGraph {
+    children: FnvHashMap<Handle, Vec<Handle>>,
+    parents: FnvHashMap<Handle, Vec<Handle>>,
+    handles: Vec<Handle>,
+}
+
+impl DirectedGraph {
+    pub fn from_gfa<T: OptFields>(gfa: &GFA<usize, T>) -> Self {
+        let mut graph = Self::default();
+
+        for seg in gfa.segments.iter() {
+            graph.handles.push(handle(seg.name, Orientation::Forward));
+            graph.handles.push(handle(seg.name, Orientation::Backward));
+        }
+
+        for link in gfa.links.iter() {
+            let from = handle(link.from_segment, link.from_orient);
+            let to = handle(link.to_segment, link.to_orient);
+            graph.add_edge(from, to);
+            if flip(to) != from {
+                graph.add_edge(flip(to), flip(from));
+            }
+        }
+
+        graph
+    }
+
+    fn add_edge(&mut self, from: Handle, to: Handle) {
+        self.children.entry(from).or_default().push(to);
+        self.parents.entry(to).or_default().push(from);
+    }
+
+    fn children(&self, h: Handle) -> &[Handle] {
+        self.children.get(&h).map_or(&[], |c| c.as_slice())
+    }
+
+    fn parents(&self, h: Handle) -> &[Handle] {
+        self.parents.get(&h).map_or(&[], |p| p.as_slice())
+    }
+
+    /// Search for the superbubble entered at `entry`, using the
+    /// algorithm from Onodera et al. (2013). Returns the exit handle
+    /// and the handles strictly inside the bubble, or `None` if there
+    /// is no superbubble starting at `entry`, or if its interior
+    /// would be larger than `max_size`.
+    fn superbubble_from(
+        &self,
+        entry: Handle,
+        max_size: usize,
+    ) -> Option<(Handle, Vec<Handle>)> {
+        if self.children(entry).is_empty() {
+            return None;
+        }
+
+        let mut stack = vec![entry];
+        let mut visited: FnvHashSet<Handle> = FnvHashSet::default();
+        let mut seen: FnvHashSet<Handle> = FnvHashSet::default();
+        seen.insert(entry);
+
+        while let Some(v) = stack.pop() {
+            visited.insert(v);
+            seen.remove(&v);
+
+            if visited.len() > max_size {
+                return None;
+            }
+
+            let children = self.children(v);
+            if children.is_empty() {
+                // Tip
+                return None;
+            }
+
+            for &u in children.iter() {
+                if u == entry {
+                    // Cycle containing the entrance
+                    return None;
+                }
+                seen.insert(u);
+                if self.parents(u).iter().all(|p| visited.contains(p)) {
+                    stack.push(u);
+                }
+            }
+
+            if stack.len() == 1 && seen.len() == 1 && seen.contains(&stack[0]) {
+                let exit = stack[0];
+                visited.remove(&entry);
+                if visited.is_empty() || self.children(exit).contains(&entry) {
+                    return None;
+                }
+                return Some((exit, visited.into_iter().collect()));
+            }
+        }
+
+        None
+    }
+}
+
+/// Find the superbubbles in the graph, along with the superbubbles
+/// each of them contains. Bubbles whose interior contains more than
+/// `max_size` oriented segments are not reported. Each bubble is
+/// reported once, in the orientation with the smaller entrance
+/// handle.
+pub fn find_superbubbles<T: OptFields>(
+    gfa: &GFA<usize, T>,
+    max_size: usize,
+) -> BubbleContainment {
+    let graph = DirectedGraph::from_gfa(gfa);
+
+    info!("Finding superbubbles");
+    let p_bar = progress_bar(graph.handles.len(), false);
+
+    let found: Vec<(Handle, Handle, Vec<Handle>)> = graph
+        .handles
+        .par_iter()
+        .progress_with(p_bar)
+        .filter_map(|&entry| {
+            let (exit, interior) = graph.superbubble_from(entry, max_size)?;
+            if handle_id(entry) == handle_id(exit) {
+                return None;
+            }
+            // Every superbubble is found once in each orientation
+            if flip(exit) < entry {
+                return None;
+            }
+            Some((entry, exit, interior))
+        })
+        .collect();
+
+    debug!("Found {} superbubbles", found.len());
+
+    let mut by_entry: FnvHashMap<Handle, Vec<(u64, u64)>> =
+        FnvHashMap::default();
+    for (entry, exit, _) in found.iter() {
+        let bubble = (handle_id(*entry), handle_id(*exit));
+        by_entry.entry(*entry).or_default().push(bubble);
+        by_entry.entry(flip(*exit)).or_default().push(bubble);
+    }
+
+    found
+        .par_iter()
+        .map(|(entry, exit, interior)| {
+            let bubble = (handle_id(*entry), handle_id(*exit));
+            let contained: FnvHashSet<(u64, u64)> = interior
+                .iter()
+                .filter_map(|h| by_entry.get(h))
+                .flatten()
+                .copied()
+                .filter(|&b| b != bubble)
+                .collect();
+            (bubble, contained.into_iter().collect())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gfa::gfa::{Link, Segment};
+
+    fn gfa_from_links(segs: usize, links: &[(usize, usize)]) -> GFA<usize, ()> {
+        let segments = (1..=segs)
+            .map(|name| Segment {
+                name,
+                sequence: "A".into(),
+                optional: (),
+            })
+            .collect();
+        let links = links
+            .iter()
+            .map(|&(from, to)| Link {
+                from_segment: from,
+                from_orient: Orientation::Forward,
+                to_segment: to,
+                to_orient: Orientation::Forward,
+                overlap: "0M".into(),
+                optional: (),
+            })
+            .collect();
+        GFA {
+            segments,
+            links,
+            ..GFA::default()
+        }
+    }
+
+    #[test]
+    fn nested_superbubbles() {
+        // 1 -> {2, 3 -> {4, 5} -> 6} -> 7
+        let gfa = gfa_from_links(
+            7,
+            &[
+                (1, 2),
+                (1, 3),
+                (2, 7),
+                (3, 4),
+                (3, 5),
+                (4, 6),
+                (5, 6),
+                (6, 7),
+            ],
+        );
+
+        let bubbles = find_superbubbles(&gfa, 100);
+        assert_eq!(bubbles.len(), 2);
+        assert_eq!(bubbles[&(1, 7)], vec![(3, 6)]);
+        assert!(bubbles[&(3, 6)].is_empty());
+    }
+
+    #[test]
+    fn cycles_are_not_superbubbles() {
+        let gfa = gfa_from_links(3, &[(1, 2), (2, 3), (3, 1)]);
+        assert!(find_superbubbles(&gfa, 100).is_empty());
+    }
+}
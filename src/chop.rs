@@ -0,0 +1,330 @@
+use bstr::{BString, ByteSlice};
+use fnv::FnvHashMap;
+
+use gfa::{
+    cigar::CIGAR,
+    gfa::{Containment, Link, Orientation, Path, Segment, GFA},
+    optfields::OptFields,
+};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+/// A segment of a chopped graph, and the part of the original
+/// segment it was cut from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChopPiece {
+    /// Name of the new segment
+    pub name: BString,
+    /// Name of the original segment
+    pub segment: BString,
+    /// 0-based offset of the new segment in the original segment
+    pub offset: usize,
+}
+
+/// The new segment names of each original segment, in order.
+type Pieces<'a> = FnvHashMap<&'a [u8], Vec<BString>>;
+
+fn piece_at_side(
+    pieces: &Pieces<'_>,
+    name: &[u8],
+    last: bool,
+) -> Option<BString> {
+    let names = pieces.get(name)?;
+    let piece = if last { names.last() } else { names.first() };
+    piece.cloned()
+}
+
+/// Split every segment longer than `max_length` into a chain of
+/// segments of at most `max_length` bases, joined by links. All
+/// segments, chopped or not, are renamed to consecutive integers
+/// starting at 1, in the order of the original segments, and the
+/// returned pieces map the new names to the old ones.
+///
+/// Links are moved to the pieces at the corresponding ends of their
+/// segments, and path steps are replaced by the pieces of their
+/// segments, in reverse order on reverse steps. Segments without a
+/// sequence are not chopped. Containments involving a chopped
+/// segment are dropped with a warning, as their positions would no
+/// longer make sense.
+pub fn chop_gfa<T: OptFields>(
+    gfa: &GFA<BString, T>,
+    max_length: usize,
+) -> (GFA<BString, T>, Vec<ChopPiece>) {
+    let max_length = max_length.max(1);
+
+    let mut segments = Vec::new();
+    let mut links = Vec::new();
+    let mut table = Vec::new();
+    let mut pieces: Pieces<'_> = FnvHashMap::default();
+
+    let zero_overlap: BString = "0M".into();
+
+    for segment in gfa.segments.iter() {
+        let seq = segment.sequence.as_slice();
+        let chunks: Vec<&[u8]> = if seq == b"*" || seq.len() <= max_length {
+            vec![seq]
+        } else {
+            seq.chunks(max_length).collect()
+        };
+
+        let mut names: Vec<BString> = Vec::with_capacity(chunks.len());
+        let mut offset = 0;
+
+        for chunk in chunks.iter() {
+            let name = BString::from((segments.len() + 1).to_string());
+
+            // The optional fields, like LN or RC, only describe the
+            // whole segment
+            let optional = if chunks.len() == 1 {
+                segment.optional.clone()
+            } else {
+                T::default()
+            };
+
+            if let Some(prev) = names.last() {
+                links.push(Link {
+                    from_segment: prev.clone(),
+                    from_orient: Orientation::Forward,
+                    to_segment: name.clone(),
+                    to_orient: Orientation::Forward,
+                    overlap: zero_overlap.clone(),
+                    optional: T::default(),
+                });
+            }
+
+            segments.push(Segment {
+                name: name.clone(),
+                sequence: chunk.as_bstr().to_owned(),
+                optional,
+            });
+            table.push(ChopPiece {
+                name: name.clone(),
+                segment: segment.name.clone(),
+                offset,
+            });
+
+            offset += chunk.len();
+            names.push(name);
+        }
+
+        pieces.insert(segment.name.as_slice(), names);
+    }
+
+    info!(
+        "Chopped {} segments into {}",
+        gfa.segments.len(),
+        segments.len()
+    );
+
+    for link in gfa.links.iter() {
+        // A link leaves the end of a forward segment, and the start
+        // of a reverse one, and vice versa for the segment it enters
+        let from = piece_at_side(
+            &pieces,
+            &link.from_segment,
+            !link.from_orient.is_reverse(),
+        );
+        let to = piece_at_side(
+            &pieces,
+            &link.to_segment,
+            link.to_orient.is_reverse(),
+        );
+
+        match (from, to) {
+            (Some(from_segment), Some(to_segment)) => links.push(Link {
+                from_segment,
+                to_segment,
+                ..link.clone()
+            }),
+            _ => warn!(
+                "Skipping link between {} and {}, which uses a missing segment",
+                link.from_segment, link.to_segment
+            ),
+        }
+    }
+
+    let mut containments: Vec<Containment<BString, T>> = Vec::new();
+    let mut dropped = 0;
+
+    for cont in gfa.containments.iter() {
+        let container = pieces.get(cont.container_name.as_slice());
+        let contained = pieces.get(cont.contained_name.as_slice());
+        match (container, contained) {
+            (Some(container), Some(contained))
+                if container.len() == 1 && contained.len() == 1 =>
+            {
+                containments.push(Containment {
+                    container_name: container[0].clone(),
+                    contained_name: contained[0].clone(),
+                    ..cont.clone()
+                });
+            }
+            _ => dropped += 1,
+        }
+    }
+
+    if dropped > 0 {
+        warn!(
+            "Dropped {} containments of chopped or missing segments",
+            dropped
+        );
+    }
+
+    let paths = gfa
+        .paths
+        .iter()
+        .map(|path| chop_path(path, &pieces))
+        .collect();
+
+    let chopped = GFA {
+        header: gfa.header.clone(),
+        segments,
+        links,
+        containments,
+        paths,
+    };
+
+    (chopped, table)
+}
+
+/// Replace the steps of the path with the pieces of their segments.
+/// The overlaps between the original steps are kept if there's one
+/// per link in the path, and the overlaps between pieces of the same
+/// segment are `0M`.
+fn chop_path<T: OptFields>(
+    path: &Path<BString, T>,
+    pieces: &Pieces<'_>,
+) -> Path<BString, T> {
+    let steps: Vec<_> = path.iter().collect();
+    let keep_overlaps = path.overlaps.len() + 1 == steps.len();
+
+    let mut segment_names = BString::from(Vec::new());
+    let mut overlaps = Vec::new();
+    let zero_overlap = CIGAR::from_bytestring(b"0M");
+
+    for (ix, (seg, orient)) in steps.iter().enumerate() {
+        let seg_pieces = match pieces.get(seg.as_bytes()) {
+            Some(seg_pieces) => seg_pieces,
+            None => {
+                warn!(
+                    "Path {} uses missing segment {}, which is skipped",
+                    path.path_name, seg
+                );
+                continue;
+            }
+        };
+
+        if keep_overlaps && !segment_names.is_empty() {
+            overlaps.push(path.overlaps[ix - 1].clone());
+        }
+
+        let mut ordered: Vec<&BString> = seg_pieces.iter().collect();
+        if orient.is_reverse() {
+            ordered.reverse();
+        }
+
+        for (piece_ix, piece) in ordered.into_iter().enumerate() {
+            if !segment_names.is_empty() {
+                segment_names.push(b',');
+            }
+            if keep_overlaps && piece_ix > 0 {
+                overlaps.push(zero_overlap.clone());
+            }
+            segment_names.extend_from_slice(piece.as_bytes());
+            segment_names.push(if orient.is_reverse() { b'-' } else { b'+' });
+        }
+    }
+
+    Path::new(
+        path.path_name.clone(),
+        segment_names,
+        overlaps,
+        path.optional.clone(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chop_segments_links_and_paths() {
+        let segment = |name: &str, seq: &str| Segment {
+            name: BString::from(name),
+            sequence: BString::from(seq),
+            optional: (),
+        };
+        let link = |from: &str, from_rev: bool, to: &str, to_rev: bool| {
+            let orient = |rev: bool| {
+                if rev {
+                    Orientation::Backward
+                } else {
+                    Orientation::Forward
+                }
+            };
+            Link {
+                from_segment: BString::from(from),
+                from_orient: orient(from_rev),
+                to_segment: BString::from(to),
+                to_orient: orient(to_rev),
+                overlap: "0M".into(),
+                optional: (),
+            }
+        };
+
+        let gfa: GFA<BString, ()> = GFA {
+            segments: vec![segment("a", "ACGTACG"), segment("b", "TT")],
+            links: vec![
+                link("a", false, "b", false),
+                link("b", false, "a", true),
+            ],
+            containments: vec![Containment {
+                container_name: "a".into(),
+                contained_name: "b".into(),
+                ..Containment::default()
+            }],
+            paths: vec![Path::new(
+                "x".into(),
+                "a+,b+,a-".into(),
+                Vec::new(),
+                (),
+            )],
+            ..GFA::default()
+        };
+
+        let (chopped, table) = chop_gfa(&gfa, 3);
+
+        let seqs: Vec<_> = chopped
+            .segments
+            .iter()
+            .map(|s| s.sequence.to_string())
+            .collect();
+        assert_eq!(seqs, vec!["ACG", "TAC", "G", "TT"]);
+
+        let offsets: Vec<_> = table
+            .iter()
+            .map(|p| (p.name.to_string(), p.segment.to_string(), p.offset))
+            .collect();
+        assert_eq!(
+            offsets,
+            vec![
+                ("1".to_string(), "a".to_string(), 0),
+                ("2".to_string(), "a".to_string(), 3),
+                ("3".to_string(), "a".to_string(), 6),
+                ("4".to_string(), "b".to_string(), 0),
+            ]
+        );
+
+        let links: Vec<_> = chopped
+            .links
+            .iter()
+            .map(|l| format!("{}{}", l.from_segment, l.to_segment))
+            .collect();
+        assert_eq!(links, vec!["12", "23", "34", "43"]);
+
+        assert!(chopped.containments.is_empty());
+
+        assert_eq!(chopped.paths[0].segment_names, "1+,2+,3+,4+,3-,2-,1-");
+    }
+}
@@ -1,14 +1,28 @@
+pub mod add_sequences;
+pub mod call;
+pub mod chop;
+pub mod components;
 pub mod convert_names;
+pub mod coverage;
+pub mod depth;
 pub mod gaf2paf;
 pub mod gfa2vcf;
+pub mod pangenome;
+pub mod path2fasta;
 pub mod saboten;
+pub mod seg2fasta;
 pub mod snps;
+pub mod sort;
 pub mod stats;
 pub mod subgraph;
+pub mod validate;
 
-use std::io::{BufReader, Read};
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+};
 
-use bstr::io::*;
+use bstr::{io::*, BString, ByteSlice};
 use gfa::{
     gfa::{SegmentId, GFA},
     optfields::OptFields,
@@ -18,12 +32,12 @@ use gfa::{
 #[allow(unused_imports)]
 use log::{debug, info, warn};
 
-pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+pub use crate::error::{Error, Result};
 
 pub fn byte_lines_iter<'a, R: Read + 'a>(
     reader: R,
-) -> Box<dyn Iterator<Item = Vec<u8>> + 'a> {
-    Box::new(BufReader::new(reader).byte_lines().map(|l| l.unwrap()))
+) -> Box<dyn Iterator<Item = std::io::Result<Vec<u8>>> + 'a> {
+    Box::new(BufReader::new(reader).byte_lines())
 }
 
 pub fn load_gfa<N, T, P>(path: P) -> Result<GFA<N, T>>
@@ -37,3 +51,66 @@ where
     let gfa = parser.parse_file(path.as_ref())?;
     Ok(gfa)
 }
+
+/// Load the regions in a BED file, as (name, start, end) triples.
+/// Header, track, and comment lines are skipped.
+pub fn load_bed_regions<P>(path: P) -> Result<Vec<(BString, usize, usize)>>
+where
+    P: AsRef<std::path::Path>,
+{
+    let file = File::open(path.as_ref())?;
+    let reader = BufReader::new(file);
+
+    let mut regions = Vec::new();
+
+    for line in reader.byte_lines() {
+        let line = line?;
+        if line.is_empty()
+            || line.starts_with(b"#")
+            || line.starts_with(b"track")
+            || line.starts_with(b"browser")
+        {
+            continue;
+        }
+
+        let mut fields = line.split_str("\t");
+        let mut next_field = || {
+            fields.next().ok_or_else(|| {
+                Error::Parse(format!(
+                    "BED line is missing fields: {}",
+                    line.as_bstr()
+                ))
+            })
+        };
+
+        let name: BString = next_field()?.into();
+        let start = next_field()?.to_str()?.parse::<usize>()?;
+        let end = next_field()?.to_str()?.parse::<usize>()?;
+
+        regions.push((name, start, end));
+    }
+
+    Ok(regions)
+}
+
+/// Parse a region given as `path:start-end`. The path name may itself
+/// contain colons.
+pub fn parse_region(region: &str) -> Result<(BString, usize, usize)> {
+    let invalid = || {
+        Error::InvalidArgument(format!(
+            "Invalid region {}, expected path:start-end",
+            region
+        ))
+    };
+    let (name, range) = region
+        .rfind(':')
+        .map(|ix| (&region[..ix], &region[ix + 1..]))
+        .ok_or_else(invalid)?;
+    let dash = range.find('-').ok_or_else(invalid)?;
+    let start = range[..dash].parse::<usize>().map_err(|_| invalid())?;
+    let end = range[dash + 1..].parse::<usize>().map_err(|_| invalid())?;
+    if start >= end {
+        return Err(invalid());
+    }
+    Ok((BString::from(name), start, end))
+}
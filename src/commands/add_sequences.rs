@@ -0,0 +1,75 @@
+use structopt::StructOpt;
+
+use bstr::BString;
+use fnv::FnvHashMap;
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::PathBuf,
+};
+
+use gfa::{gfa::GFA, optfields::OptionalFields, writer::gfa_string};
+
+use crate::sequence;
+
+use super::{load_gfa, Error, Result};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+/// Fill in the sequences of the segments that have `*` as sequence
+/// from a FASTA file, matching FASTA records to segments by name.
+///
+/// Sequences whose length doesn't match the segment's LN tag are not
+/// used.
+#[derive(StructOpt, Debug)]
+pub struct AddSequencesArgs {
+    #[structopt(
+        name = "path to FASTA file",
+        long = "fasta",
+        parse(from_os_str)
+    )]
+    fasta: PathBuf,
+    /// Fail if any segment is still missing its sequence
+    #[structopt(name = "require all", long = "require-all")]
+    require_all: bool,
+    /// Write the GFA to this file instead of stdout
+    #[structopt(name = "output file", short = "o", long = "out")]
+    out: Option<PathBuf>,
+}
+
+pub fn add_sequences(
+    gfa_path: &PathBuf,
+    args: &AddSequencesArgs,
+) -> Result<()> {
+    let mut gfa: GFA<BString, OptionalFields> = load_gfa(gfa_path)?;
+
+    let reader = BufReader::new(File::open(&args.fasta)?);
+    let sequences: FnvHashMap<BString, Vec<u8>> =
+        sequence::read_fasta(reader)?.into_iter().collect();
+    info!("Loaded {} FASTA sequences", sequences.len());
+
+    let missing = sequence::attach_sequences(&mut gfa, &sequences);
+
+    if !missing.is_empty() {
+        for name in missing.iter().take(10) {
+            debug!("Segment {} is missing its sequence", name);
+        }
+        if args.require_all {
+            return Err(Error::InvalidGraph(format!(
+                "{} segments are missing their sequence",
+                missing.len()
+            )));
+        }
+        warn!("{} segments are missing their sequence", missing.len());
+    }
+
+    let mut out: Box<dyn Write> = match &args.out {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(std::io::stdout())),
+    };
+    writeln!(out, "{}", gfa_string(&gfa))?;
+    out.flush()?;
+
+    Ok(())
+}
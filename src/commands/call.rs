@@ -0,0 +1,139 @@
+use structopt::StructOpt;
+
+use std::path::PathBuf;
+
+use gfa::gfa::GFA;
+
+use crate::{
+    bubbles, bubbles::Bubble, depth, gaf_convert, genotype,
+    genotype::Traversal, variants, variants::vcf::VCFHeader,
+};
+
+use super::saboten::BubbleKindArgs;
+
+use super::{load_gfa, Error, Result};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+/// Genotype a sample at the graph's ultrabubbles, using the reads
+/// aligned to the graph in a GAF file, and output a single-sample VCF.
+///
+/// The alleles of each bubble are the distinct traversals of the
+/// paths through it, and the reads supporting each allele are those
+/// that traverse the entire bubble along it. Diploid genotypes are
+/// called from the allele read counts.
+///
+/// The provided GFA file should be the same as the one used to create the GAF.
+#[derive(StructOpt, Debug)]
+pub struct CallArgs {
+    #[structopt(name = "path to GAF file", long = "gaf", parse(from_os_str))]
+    gaf: PathBuf,
+    /// The path to report the variants on
+    #[structopt(name = "reference path", long = "ref")]
+    ref_path: String,
+    /// The name of the sample column in the VCF
+    #[structopt(
+        name = "sample name",
+        long = "sample",
+        default_value = "SAMPLE"
+    )]
+    sample: String,
+    /// Skip alignments with a lower mapping quality
+    #[structopt(name = "minimum MAPQ", long = "min-mapq", default_value = "0")]
+    min_mapq: u8,
+    /// The probability that a read supports the wrong allele, above 0
+    /// and below 0.5
+    #[structopt(
+        name = "error rate",
+        long = "error-rate",
+        default_value = "0.01"
+    )]
+    error_rate: f64,
+    /// Load ultrabubbles from a file instead of calculating them.
+    #[structopt(name = "ultrabubbles file", long = "ultrabubbles")]
+    ultrabubbles_file: Option<PathBuf>,
+    #[structopt(flatten)]
+    bubble_kind: BubbleKindArgs,
+}
+
+pub fn call(gfa_path: &PathBuf, args: &CallArgs) -> Result<()> {
+    if !(args.error_rate > 0.0 && args.error_rate < 0.5) {
+        return Err(Error::InvalidArgument(
+            "The error rate must be above 0 and below 0.5".to_string(),
+        ));
+    }
+
+    let (bubbles, path_data) = {
+        let gfa: GFA<usize, ()> = load_gfa(gfa_path)?;
+
+        let bubbles: Vec<(u64, u64)> =
+            if let Some(path) = &args.ultrabubbles_file {
+                super::saboten::load_bubbles(path)?
+                    .iter()
+                    .map(Bubble::ends)
+                    .collect()
+            } else {
+                super::saboten::find_bubbles_in_gfa(&gfa, &args.bubble_kind)
+                    .keys()
+                    .copied()
+                    .collect()
+            };
+
+        (bubbles, variants::gfa_path_data(gfa)?)
+    };
+
+    let ref_ix =
+        depth::path_index(&path_data, &args.ref_path).ok_or_else(|| {
+            Error::InvalidGraph(format!(
+                "Reference path {} does not exist",
+                args.ref_path
+            ))
+        })?;
+
+    let bubble_nodes = bubbles::bubble_node_set(bubbles.iter().copied());
+    let path_indices =
+        variants::bubble_path_indices(&path_data.paths, &bubble_nodes);
+
+    let sites =
+        genotype::bubble_sites(&path_data, &path_indices, ref_ix, &bubbles);
+    info!(
+        "Found {} variable sites on {} in {} bubbles",
+        sites.len(),
+        args.ref_path,
+        bubbles.len()
+    );
+
+    let gafs = gaf_convert::load_gaf(&args.gaf)?;
+    let reads: Vec<Traversal> = gafs
+        .iter()
+        .filter(|gaf| gaf.quality >= args.min_mapq)
+        .filter_map(genotype::gaf_node_steps)
+        .collect();
+    info!(
+        "Using {} of {} alignments with mapping quality at least {}",
+        reads.len(),
+        gafs.len(),
+        args.min_mapq
+    );
+
+    let support = genotype::allele_support(&sites, &reads);
+
+    let vcf_header = VCFHeader::new(gfa_path).with_sample(args.sample.as_str());
+    println!("{}", vcf_header);
+
+    let ref_name = &path_data.path_names[ref_ix];
+
+    for (site, support) in sites.iter().zip(support.iter()) {
+        let genotype = genotype::call_genotype(support, args.error_rate);
+        let record = genotype::site_vcf_record(
+            ref_name,
+            site,
+            support,
+            genotype.as_ref(),
+        );
+        println!("{}", record);
+    }
+
+    Ok(())
+}
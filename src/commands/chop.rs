@@ -0,0 +1,74 @@
+use structopt::StructOpt;
+
+use bstr::BString;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use gfa::{gfa::GFA, optfields::OptionalFields, writer::gfa_string};
+
+use crate::chop;
+
+use super::{load_gfa, Error, Result};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+/// Split the segments longer than the maximum length into chains of
+/// shorter segments, rewriting the links and paths.
+///
+/// All segments are renamed to consecutive integers, and a table of
+/// the new segment names with the original segment and offset each
+/// was cut from is written alongside the GFA. Containments of chopped
+/// segments are dropped.
+#[derive(StructOpt, Debug)]
+pub struct ChopArgs {
+    /// The maximum length of the segments in the chopped graph
+    #[structopt(name = "max length", long = "max-length", short = "l")]
+    max_length: usize,
+    /// Write the translation table, with the columns new segment,
+    /// original segment, and 0-based offset, to this file
+    #[structopt(name = "translation table", long = "translation")]
+    translation: Option<PathBuf>,
+    /// Write the chopped GFA to this file instead of stdout
+    #[structopt(name = "output file", short = "o", long = "out")]
+    out: Option<PathBuf>,
+}
+
+pub fn chop(gfa_path: &PathBuf, args: &ChopArgs) -> Result<()> {
+    if args.max_length == 0 {
+        return Err(Error::InvalidArgument(
+            "The maximum segment length must be at least 1".to_string(),
+        ));
+    }
+
+    let gfa: GFA<BString, OptionalFields> = load_gfa(gfa_path)?;
+
+    let (chopped, table) = chop::chop_gfa(&gfa, args.max_length);
+
+    let mut out: Box<dyn Write> = match &args.out {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(std::io::stdout())),
+    };
+
+    writeln!(out, "{}", gfa_string(&chopped))?;
+    out.flush()?;
+
+    if let Some(path) = &args.translation {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "#segment\toriginal\toffset")?;
+        for piece in table.iter() {
+            writeln!(
+                out,
+                "{}\t{}\t{}",
+                piece.name, piece.segment, piece.offset
+            )?;
+        }
+        out.flush()?;
+        info!("Saved translation table to {}", path.display());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,189 @@
+use structopt::StructOpt;
+
+use bstr::{BString, ByteSlice};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use gfa::{
+    gfa::GFA,
+    optfields::{OptFields, OptionalFields},
+    writer::gfa_string,
+};
+
+use crate::subgraph;
+
+use super::{load_gfa, Result};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+/// Split the input GFA into its weakly connected components, writing
+/// each component to its own GFA file, and print a table of the
+/// component sizes.
+///
+/// Components are named after the path with the most steps in them,
+/// or by their index if they contain no paths. Paths that cross
+/// components are split into pieces, which are matched by the name of
+/// the original path.
+#[derive(StructOpt, Debug)]
+pub struct ComponentsArgs {
+    /// Directory to write the component GFAs to
+    #[structopt(
+        name = "output directory",
+        long = "out-dir",
+        short = "o",
+        default_value = "."
+    )]
+    out_dir: PathBuf,
+    /// Prefix of the component file names, which are
+    /// `<prefix>.<name>.gfa`
+    #[structopt(name = "prefix", long = "prefix", default_value = "component")]
+    prefix: String,
+    /// Name components after the reference path they contain, and
+    /// write all components without a reference path to a single
+    /// `<prefix>.unassigned.gfa` file
+    #[structopt(name = "reference paths", long = "ref")]
+    ref_paths: Option<Vec<String>>,
+    /// Only print the size table, without writing any GFAs
+    #[structopt(name = "report only", long = "report-only")]
+    report_only: bool,
+}
+
+/// The name of the path a path piece was cut from, without the
+/// `:start-end` suffix given to paths that cross components.
+fn original_path_name(name: &[u8]) -> &[u8] {
+    let is_range = |range: &[u8]| {
+        let mut parts = range.splitn_str(2, "-");
+        let is_num = |p: Option<&[u8]>| {
+            p.is_some_and(|p| !p.is_empty() && p.iter().all(u8::is_ascii_digit))
+        };
+        is_num(parts.next()) && is_num(parts.next())
+    };
+    match name.rfind_byte(b':') {
+        Some(ix) if is_range(&name[ix + 1..]) => &name[..ix],
+        _ => name,
+    }
+}
+
+/// The path with the most steps in the component, if any.
+fn dominant_path<T: OptFields>(gfa: &GFA<BString, T>) -> Option<&[u8]> {
+    gfa.paths
+        .iter()
+        .max_by_key(|p| p.iter().count())
+        .map(|p| original_path_name(&p.path_name))
+}
+
+/// Make a path name safe to use in a file name.
+fn file_name_part(name: &[u8]) -> String {
+    name.to_str_lossy().replace('/', "_")
+}
+
+fn merge_into<T: OptFields>(
+    target: &mut GFA<BString, T>,
+    gfa: GFA<BString, T>,
+) {
+    target.segments.extend(gfa.segments);
+    target.links.extend(gfa.links);
+    target.containments.extend(gfa.containments);
+    target.paths.extend(gfa.paths);
+}
+
+pub fn components(gfa_path: &PathBuf, args: &ComponentsArgs) -> Result<()> {
+    let gfa: GFA<BString, OptionalFields> = load_gfa(gfa_path)?;
+
+    let graphs = subgraph::split_components(&gfa);
+    info!("Found {} connected components", graphs.len());
+
+    // With reference paths, the components are grouped by the first
+    // reference path they contain, and the rest are merged into one
+    let mut named: Vec<(String, GFA<BString, OptionalFields>)> = Vec::new();
+
+    if let Some(ref_paths) = &args.ref_paths {
+        let mut groups: HashMap<String, usize> = HashMap::new();
+
+        for graph in graphs {
+            let ref_path = graph
+                .paths
+                .iter()
+                .map(|p| original_path_name(&p.path_name))
+                .map(|name| name.to_str_lossy().into_owned())
+                .find(|name| ref_paths.contains(name));
+
+            let name = match ref_path {
+                Some(name) => file_name_part(name.as_bytes()),
+                None => "unassigned".to_string(),
+            };
+
+            if let Some(&ix) = groups.get(&name) {
+                merge_into(&mut named[ix].1, graph);
+            } else {
+                groups.insert(name.clone(), named.len());
+                named.push((name, graph));
+            }
+        }
+    } else {
+        for (ix, graph) in graphs.into_iter().enumerate() {
+            let name = dominant_path(&graph)
+                .map(file_name_part)
+                .unwrap_or_else(|| ix.to_string());
+            named.push((name, graph));
+        }
+    }
+
+    println!("#component\tfile\tsegments\tlinks\tpaths\tbases");
+
+    let mut used_names: HashMap<String, usize> = HashMap::new();
+
+    for (ix, (name, graph)) in named.iter().enumerate() {
+        // Several components can share a dominant path name if the
+        // path isn't contiguous
+        let count = used_names.entry(name.clone()).or_insert(0);
+        *count += 1;
+        let name = if *count > 1 {
+            format!("{}.{}", name, count)
+        } else {
+            name.clone()
+        };
+
+        let file_path =
+            args.out_dir.join(format!("{}.{}.gfa", args.prefix, name));
+
+        let bases: usize =
+            graph.segments.iter().map(subgraph::segment_length).sum();
+
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            ix,
+            file_path.display(),
+            graph.segments.len(),
+            graph.links.len(),
+            graph.paths.len(),
+            bases
+        );
+
+        if !args.report_only {
+            let mut out = BufWriter::new(File::create(&file_path)?);
+            writeln!(out, "{}", gfa_string(graph))?;
+            out.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn piece_names() {
+        assert_eq!(original_path_name(b"x:0-2"), b"x");
+        assert_eq!(original_path_name(b"HG002#1#chr1:10-25"), b"HG002#1#chr1");
+        assert_eq!(original_path_name(b"chr1:a-2"), b"chr1:a-2");
+        assert_eq!(original_path_name(b"chr1"), b"chr1");
+    }
+}
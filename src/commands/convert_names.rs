@@ -1,7 +1,11 @@
 use structopt::StructOpt;
 
 use bstr::BString;
-use std::{fs::File, io::Write, path::PathBuf};
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
 
 use gfa::{
     gfa::{name_conversion::NameMap, GFA},
@@ -9,7 +13,7 @@ use gfa::{
     writer::write_gfa,
 };
 
-use super::{load_gfa, Result};
+use super::{load_gfa, Error, Result};
 
 #[derive(StructOpt, Debug)]
 /// Convert a GFA with string names to one with integer names, and
@@ -33,32 +37,41 @@ pub struct GfaIdConvertArgs {
     check_hash: bool,
 }
 
-fn gfa_to_name_map_path(path: &PathBuf) -> PathBuf {
-    let mut new_path: PathBuf = path.clone();
-    let old_name = new_path.file_stem().and_then(|p| p.to_str()).unwrap();
+fn file_stem(path: &Path) -> Result<&str> {
+    path.file_stem().and_then(|p| p.to_str()).ok_or_else(|| {
+        Error::InvalidArgument(format!(
+            "Invalid GFA file name {}",
+            path.display()
+        ))
+    })
+}
+
+pub(crate) fn gfa_to_name_map_path(path: &Path) -> Result<PathBuf> {
+    let mut new_path: PathBuf = path.to_path_buf();
+    let old_name = file_stem(path)?;
     let new_name = format!("{}.name_map.json", old_name);
     new_path.set_file_name(&new_name);
-    new_path
+    Ok(new_path)
 }
 
-fn converted_gfa_path(path: &PathBuf) -> PathBuf {
-    let mut new_path: PathBuf = path.clone();
-    let old_name = new_path.file_stem().and_then(|p| p.to_str()).unwrap();
+fn converted_gfa_path(path: &Path) -> Result<PathBuf> {
+    let mut new_path: PathBuf = path.to_path_buf();
+    let old_name = file_stem(path)?;
     let new_name = format!("{}.uint_ids.gfa", old_name);
     new_path.set_file_name(&new_name);
-    new_path
+    Ok(new_path)
 }
 
-fn restored_gfa_path(path: &PathBuf) -> PathBuf {
-    let mut new_path: PathBuf = path.clone();
-    let old_name = new_path.file_stem().and_then(|p| p.to_str()).unwrap();
+fn restored_gfa_path(path: &Path) -> Result<PathBuf> {
+    let mut new_path: PathBuf = path.to_path_buf();
+    let old_name = file_stem(path)?;
     let new_name = format!("{}.str_ids.gfa", old_name);
     new_path.set_file_name(&new_name);
-    new_path
+    Ok(new_path)
 }
 
 fn segment_id_to_usize(
-    gfa_path: &PathBuf,
+    gfa_path: &Path,
     gfa: &GFA<BString, OptionalFields>,
     args: &GfaIdConvertArgs,
 ) -> Result<()> {
@@ -70,7 +83,7 @@ fn segment_id_to_usize(
 
     if let Some(new_gfa) = name_map.gfa_bstring_to_usize(&gfa, args.check_hash)
     {
-        let new_gfa_path = converted_gfa_path(&gfa_path);
+        let new_gfa_path = converted_gfa_path(gfa_path)?;
         let mut new_gfa_file = File::create(new_gfa_path.clone())?;
         let mut gfa_str = String::new();
         write_gfa(&new_gfa, &mut gfa_str);
@@ -78,7 +91,7 @@ fn segment_id_to_usize(
         println!("Saved converted GFA to {}", new_gfa_path.display());
 
         if args.name_map_path.is_none() {
-            let name_map_path = gfa_to_name_map_path(&gfa_path);
+            let name_map_path = gfa_to_name_map_path(gfa_path)?;
             name_map.save_json(&name_map_path)?;
             println!("Saved new name map to {}", name_map_path.display());
         }
@@ -90,21 +103,24 @@ fn segment_id_to_usize(
 }
 
 fn segment_id_to_bstring(
-    gfa_path: &PathBuf,
+    gfa_path: &Path,
     gfa: &GFA<usize, OptionalFields>,
     args: &GfaIdConvertArgs,
 ) -> Result<()> {
-    let name_map_path = args
-        .name_map_path
-        .as_ref()
-        .expect("Need name map to convert back");
+    let name_map_path = args.name_map_path.as_ref().ok_or_else(|| {
+        Error::InvalidArgument("Need name map to convert back".to_string())
+    })?;
     let name_map = NameMap::load_json(&name_map_path)?;
 
-    let new_gfa: GFA<BString, OptionalFields> = name_map
-        .gfa_usize_to_bstring(&gfa)
-        .expect("Error during conversion -- is it the right name map?");
+    let new_gfa: GFA<BString, OptionalFields> =
+        name_map.gfa_usize_to_bstring(gfa).ok_or_else(|| {
+            Error::InvalidArgument(
+                "Error during conversion -- is it the right name map?"
+                    .to_string(),
+            )
+        })?;
 
-    let new_gfa_path = restored_gfa_path(gfa_path);
+    let new_gfa_path = restored_gfa_path(gfa_path)?;
     let mut new_gfa_file = File::create(new_gfa_path.clone())?;
     let mut gfa_str = String::new();
     write_gfa(&new_gfa, &mut gfa_str);
@@ -140,7 +156,7 @@ mod tests {
     fn name_map_path_correct() {
         let gfa_path =
             PathBuf::from("../../some_folder/another/some_gfa_file.gfa");
-        let new_path = gfa_to_name_map_path(&gfa_path);
+        let new_path = gfa_to_name_map_path(&gfa_path).unwrap();
         assert_eq!(
             Some("../../some_folder/another/some_gfa_file.name_map.json"),
             new_path.to_str()
@@ -150,7 +166,7 @@ mod tests {
     #[test]
     fn converted_gfa_path_correct() {
         let gfa_path = PathBuf::from("some_gfa_file.gfa");
-        let new_path = converted_gfa_path(&gfa_path);
+        let new_path = converted_gfa_path(&gfa_path).unwrap();
         assert_eq!(Some("some_gfa_file.uint_ids.gfa"), new_path.to_str());
     }
 }
@@ -0,0 +1,91 @@
+use clap::arg_enum;
+use structopt::StructOpt;
+
+use bstr::BString;
+use std::path::PathBuf;
+
+use gfa::{
+    gfa::GFA,
+    optfields::{OptField, OptFieldVal, OptionalFields},
+    writer::gfa_string,
+};
+
+use crate::{gaf_convert, subgraph};
+
+use super::{load_gfa, Result};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+arg_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum CoverageFormat {
+        Tsv,
+        Gfa,
+    }
+}
+
+/// Compute the read coverage of each segment from a GAF file.
+///
+/// For each segment, the number of alignments overlapping it and the
+/// number of its bases covered by them are reported. As a GFA, these
+/// are added to the S-lines as the `RC:i` (read count) and `DP:f`
+/// (mean depth) tags. Segments are sorted by name in the output.
+///
+/// The provided GFA file should be the same as the one used to create the GAF.
+#[derive(StructOpt, Debug)]
+pub struct CoverageArgs {
+    #[structopt(name = "path to GAF file", long = "gaf", parse(from_os_str))]
+    gaf: PathBuf,
+    /// Skip alignments with a lower mapping quality
+    #[structopt(name = "minimum MAPQ", long = "min-mapq", default_value = "0")]
+    min_mapq: u8,
+    /// Output format, one of tsv, gfa
+    #[structopt(
+        name = "output format",
+        long = "format",
+        default_value = "tsv",
+        possible_values = &CoverageFormat::variants(),
+        case_insensitive = true
+    )]
+    format: CoverageFormat,
+}
+
+pub fn coverage(gfa_path: &PathBuf, args: &CoverageArgs) -> Result<()> {
+    let mut gfa: GFA<BString, OptionalFields> = load_gfa(gfa_path)?;
+
+    let coverage =
+        gaf_convert::gaf_coverage(&mut gfa, &args.gaf, args.min_mapq)?;
+
+    match args.format {
+        CoverageFormat::Tsv => {
+            println!("#segment\tlength\talignments\tbases\tdepth");
+            for (seg, cov) in gfa.segments.iter().zip(coverage.iter()) {
+                let len = subgraph::segment_length(seg);
+                println!(
+                    "{}\t{}\t{}\t{}\t{:.2}",
+                    seg.name,
+                    len,
+                    cov.alignments,
+                    cov.bases,
+                    cov.depth(len)
+                );
+            }
+        }
+        CoverageFormat::Gfa => {
+            for (seg, cov) in gfa.segments.iter_mut().zip(coverage.iter()) {
+                let depth = cov.depth(subgraph::segment_length(seg)) as f32;
+                seg.optional.retain(|o| &o.tag != b"RC" && &o.tag != b"DP");
+                seg.optional.push(OptField::new(
+                    b"RC",
+                    OptFieldVal::Int(cov.alignments as i64),
+                ));
+                seg.optional
+                    .push(OptField::new(b"DP", OptFieldVal::Float(depth)));
+            }
+            println!("{}", gfa_string(&gfa));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,101 @@
+use clap::arg_enum;
+use structopt::StructOpt;
+
+use std::path::PathBuf;
+
+use gfa::gfa::GFA;
+
+use crate::{depth, variants};
+
+use super::{load_gfa, Error, Result};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+arg_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum DepthBy {
+        Steps,
+        Paths,
+        Samples,
+    }
+}
+
+/// Report, for each segment, how many path steps traverse it, and
+/// how many distinct paths and samples do.
+///
+/// Samples are taken from PanSN path names, `sample#haplotype#contig`.
+/// With --ref, the depth is instead projected onto the reference path
+/// and printed as a bedGraph.
+#[derive(StructOpt, Debug)]
+pub struct DepthArgs {
+    /// Print the depth along this path as a bedGraph
+    #[structopt(name = "reference path", long = "ref")]
+    ref_path: Option<String>,
+    /// The depth to use in the bedGraph, one of steps, paths, samples
+    #[structopt(
+        name = "depth by",
+        long = "depth-by",
+        default_value = "steps",
+        possible_values = &DepthBy::variants(),
+        case_insensitive = true
+    )]
+    depth_by: DepthBy,
+}
+
+pub fn depth(gfa_path: &PathBuf, args: &DepthArgs) -> Result<()> {
+    let path_data = {
+        let gfa: GFA<usize, ()> = load_gfa(gfa_path)?;
+        info!("GFA has {} paths", gfa.paths.len());
+        variants::gfa_path_data(gfa)?
+    };
+
+    let ref_ix = args
+        .ref_path
+        .as_ref()
+        .map(|name| {
+            depth::path_index(&path_data, name).ok_or_else(|| {
+                Error::InvalidGraph(format!(
+                    "Reference path {} does not exist",
+                    name
+                ))
+            })
+        })
+        .transpose()?;
+
+    let index = depth::node_occurrence_index(&path_data.paths);
+    let depths = depth::segment_depths(&path_data, &index);
+
+    if let Some(ref_ix) = ref_ix {
+        let ref_name = &path_data.path_names[ref_ix];
+        let value = |node: u64| {
+            let depth = depths.get(&node).copied().unwrap_or_default();
+            match args.depth_by {
+                DepthBy::Steps => depth.steps,
+                DepthBy::Paths => depth.paths,
+                DepthBy::Samples => depth.samples,
+            }
+        };
+        for (start, end, value) in
+            depth::path_bedgraph(&path_data, ref_ix, value)
+        {
+            println!("{}\t{}\t{}\t{}", ref_name, start, end, value);
+        }
+        return Ok(());
+    }
+
+    let mut nodes: Vec<_> = depths.keys().copied().collect();
+    nodes.sort_unstable();
+
+    println!("#segment\tlength\tsteps\tpaths\tsamples");
+    for node in nodes {
+        let depth = depths[&node];
+        let len = path_data.segment_map[&(node as usize)].len();
+        println!(
+            "{}\t{}\t{}\t{}\t{}",
+            node, len, depth.steps, depth.paths, depth.samples
+        );
+    }
+
+    Ok(())
+}
@@ -22,15 +22,14 @@ pub struct GAF2PAFArgs {
 pub fn gaf2paf(gfa_path: &PathBuf, args: &GAF2PAFArgs) -> Result<()> {
     let gfa: GFA<BString, OptionalFields> = load_gfa(gfa_path)?;
 
-    let paf_lines = gaf_convert::gaf_to_paf(gfa, &args.gaf);
+    let paf_lines = gaf_convert::gaf_to_paf(gfa, &args.gaf)?;
 
     if let Some(out_path) = &args.out {
-        let mut out_file =
-            File::create(&out_path).expect("Error creating PAF output file");
+        let mut out_file = File::create(out_path)?;
 
-        paf_lines.iter().for_each(|p| {
-            writeln!(out_file, "{}", p).unwrap();
-        });
+        for p in paf_lines.iter() {
+            writeln!(out_file, "{}", p)?;
+        }
     } else {
         paf_lines.iter().for_each(|p| println!("{}", p));
     }
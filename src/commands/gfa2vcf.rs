@@ -4,7 +4,11 @@ use indicatif::{
     ParallelProgressIterator, ProgressBar, ProgressIterator, ProgressStyle,
 };
 use rayon::prelude::*;
-use std::{fs::File, io::BufReader, path::PathBuf};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::PathBuf,
+};
 use structopt::StructOpt;
 
 #[allow(unused_imports)]
@@ -12,9 +16,17 @@ use log::{debug, info, log_enabled, warn};
 
 use gfa::gfa::GFA;
 
-use crate::{util::progress_bar, variants, variants::PathStep};
+use crate::{
+    bubbles,
+    bubbles::Bubble,
+    util::progress_bar,
+    variants,
+    variants::{PathData, PathIndices, PathStep},
+};
+
+use super::saboten::{BubbleFilterArgs, BubbleKindArgs, SkipReason};
 
-use super::{load_gfa, Result};
+use super::{load_gfa, Error, Result};
 
 /// Output a VCF for the given GFA, using the graph's ultrabubbles to
 /// identify areas of variation.
@@ -27,6 +39,8 @@ pub struct GFA2VCFArgs {
         short = "ub"
     )]
     ultrabubbles_file: Option<PathBuf>,
+    #[structopt(flatten)]
+    bubble_kind: BubbleKindArgs,
     /// Don't compare two paths if their start and end orientations
     /// don't match each other
     #[structopt(name = "ignore inverted paths", long = "no-inv")]
@@ -38,6 +52,21 @@ pub struct GFA2VCFArgs {
     ref_paths_file: Option<PathBuf>,
     #[structopt(name = "list of paths to use as references", long = "refs")]
     ref_paths_vec: Option<Vec<String>>,
+    #[structopt(flatten)]
+    bubble_filter: BubbleFilterArgs,
+    /// Split bubbles that exceed --max-nodes or --max-allele-len at
+    /// the nodes shared by all paths through them, instead of
+    /// skipping them.
+    #[structopt(name = "split large bubbles", long = "split-large")]
+    split_large: bool,
+    /// Only use bubbles overlapping the regions in this BED file. The
+    /// region names must be path names.
+    #[structopt(name = "regions BED file", long = "regions")]
+    regions: Option<PathBuf>,
+    /// Write the bubbles that were skipped, and the reason, to this
+    /// file.
+    #[structopt(name = "skipped bubbles report", long = "skipped-report")]
+    skipped_report: Option<PathBuf>,
 }
 
 fn load_paths_file(file_path: PathBuf) -> Result<Vec<BString>> {
@@ -70,7 +99,7 @@ pub fn gfa2vcf(gfa_path: &PathBuf, args: GFA2VCFArgs) -> Result<()> {
     let ref_path_names: Option<FnvHashSet<BString>> = {
         let ref_paths: FnvHashSet<BString> = ref_paths_list
             .into_iter()
-            .chain(ref_paths_file.into_iter())
+            .chain(ref_paths_file)
             .collect();
         if ref_paths.is_empty() {
             None
@@ -85,38 +114,83 @@ pub fn gfa2vcf(gfa_path: &PathBuf, args: GFA2VCFArgs) -> Result<()> {
         }
     };
 
-    let path_data = {
-        let gfa: GFA<usize, ()> = load_gfa(&gfa_path)?;
+    // The GFA is only parsed once; the bubbles are found before the
+    // GFA is consumed to build the path data
+    let (mut bubbles, contained, path_data) = {
+        let gfa: GFA<usize, ()> = load_gfa(gfa_path)?;
 
         if gfa.paths.len() < 2 {
-            panic!("GFA must contain at least two paths");
+            return Err(Error::InvalidGraph(
+                "GFA must contain at least two paths".to_string(),
+            ));
         }
 
         info!("GFA has {} paths", gfa.paths.len());
 
-        variants::gfa_path_data(gfa)
+        let (bubbles, contained) = if let Some(path) = &args.ultrabubbles_file {
+            (super::saboten::load_bubbles(path)?, None)
+        } else {
+            let contained =
+                super::saboten::find_bubbles_in_gfa(&gfa, &args.bubble_kind);
+            let bubbles: Vec<_> =
+                contained.keys().map(|&(a, b)| Bubble::new(a, b)).collect();
+            (bubbles, Some(contained))
+        };
+
+        (bubbles, contained, variants::gfa_path_data(gfa)?)
     };
 
-    let mut ultrabubbles = if let Some(path) = &args.ultrabubbles_file {
-        super::saboten::load_ultrabubbles(path)
-    } else {
-        super::saboten::find_ultrabubbles(gfa_path)
-    }?;
+    info!("Found {} ultrabubbles", bubbles.len());
 
-    info!("Using {} ultrabubbles", ultrabubbles.len());
+    let ultrabubble_nodes =
+        bubbles::bubble_node_set(bubbles.iter().map(Bubble::ends));
+
+    let mut path_indices =
+        variants::bubble_path_indices(&path_data.paths, &ultrabubble_nodes);
+
+    if args.bubble_filter.is_active() {
+        if let Some(contained) = &contained {
+            bubbles =
+                bubbles::annotate_bubbles(&path_data, &path_indices, contained);
+        } else {
+            // Bubbles from a plain file would all be treated as
+            // top-level bubbles, silently passing --max-depth
+            if args.bubble_filter.uses_depth()
+                && bubbles.iter().any(|b| b.info.is_none())
+            {
+                return Err(Error::InvalidArgument(
+                    "--max-depth needs the bubble nesting, use a bubble \
+                     file from `ultrabubbles --format tsv`"
+                        .to_string(),
+                ));
+            }
+            bubbles::fill_path_info(&path_data, &path_indices, &mut bubbles);
+        }
+    }
 
-    ultrabubbles.sort();
+    let (ultrabubbles, skipped) = select_bubbles(
+        &args.bubble_filter,
+        args.split_large,
+        args.regions.as_ref(),
+        &path_data,
+        &mut path_indices,
+        &bubbles,
+    )?;
 
-    let ultrabubble_nodes = ultrabubbles
-        .iter()
-        .flat_map(|&(a, b)| {
-            use std::iter::once;
-            once(a).chain(once(b))
-        })
-        .collect::<FnvHashSet<_>>();
+    info!(
+        "Using {} ultrabubbles, skipped {}",
+        ultrabubbles.len(),
+        skipped.len()
+    );
 
-    let path_indices =
-        variants::bubble_path_indices(&path_data.paths, &ultrabubble_nodes);
+    if let Some(report_path) = &args.skipped_report {
+        let mut report = BufWriter::new(File::create(report_path)?);
+        writeln!(report, "#from\tto\treason")?;
+        for ((from, to), reason) in skipped.iter() {
+            writeln!(report, "{}\t{}\t{}", from, to, reason)?;
+        }
+        report.flush()?;
+    }
 
     let mut all_vcf_records = Vec::new();
 
@@ -197,6 +271,133 @@ pub fn gfa2vcf(gfa_path: &PathBuf, args: GFA2VCFArgs) -> Result<()> {
     */
 }
 
+type SkippedBubbles = Vec<((u64, u64), SkipReason)>;
+
+/// Choose the bubbles to call variants in, applying the bubble
+/// filter and region restriction, and splitting large bubbles if
+/// requested. Returns the chosen bubbles, sorted, along with the
+/// skipped bubbles and the reason each was skipped. If new bubbles
+/// are created by splitting, their nodes are added to the path
+/// indices, and they're filtered again; parts that are still too
+/// large are skipped.
+fn select_bubbles(
+    filter: &BubbleFilterArgs,
+    split_large: bool,
+    regions: Option<&PathBuf>,
+    path_data: &PathData,
+    path_indices: &mut PathIndices,
+    bubbles: &[Bubble],
+) -> Result<(Vec<(u64, u64)>, SkippedBubbles)> {
+    let mut selected = Vec::with_capacity(bubbles.len());
+    let mut skipped = Vec::new();
+    let mut to_split = Vec::new();
+
+    for bubble in bubbles.iter() {
+        let reason = bubble.info.and_then(|i| filter.skip_reason(&i));
+        match reason {
+            None => selected.push(bubble.ends()),
+            Some(r) if split_large && r.is_size() => {
+                to_split.push((bubble.ends(), bubble.info))
+            }
+            Some(r) => skipped.push((bubble.ends(), r)),
+        }
+    }
+
+    if !to_split.is_empty() {
+        let split: Vec<_> = to_split
+            .par_iter()
+            .map(|&((from, to), info)| {
+                let parts =
+                    bubbles::split_bubble(path_data, path_indices, from, to);
+                ((from, to), info, parts)
+            })
+            .collect();
+
+        let mut new_bubbles = Vec::new();
+        for (bubble, info, parts) in split {
+            if parts.is_empty() {
+                skipped.push((bubble, SkipReason::NoSplit));
+            } else {
+                new_bubbles.extend(parts.into_iter().map(|part| (part, info)));
+            }
+        }
+
+        info!(
+            "Split {} large bubbles into {} smaller bubbles",
+            to_split.len(),
+            new_bubbles.len()
+        );
+
+        let new_nodes: FnvHashSet<u64> =
+            bubbles::bubble_node_set(new_bubbles.iter().map(|&(b, _)| b))
+                .into_iter()
+                .filter(|n| !path_indices.contains_key(n))
+                .collect();
+        path_indices.extend(variants::bubble_path_indices(
+            &path_data.paths,
+            &new_nodes,
+        ));
+
+        // The parts take the place of the split bubble in the nesting
+        for ((from, to), parent_info) in new_bubbles {
+            let mut info =
+                bubbles::bubble_path_info(path_data, path_indices, from, to);
+            if let Some(parent_info) = parent_info {
+                info.parent = parent_info.parent;
+                info.depth = parent_info.depth;
+            }
+            match filter.skip_reason(&info) {
+                None => selected.push((from, to)),
+                Some(reason) => {
+                    debug!(
+                        "Skipping part {}-{} of a split bubble: {}",
+                        from, to, reason
+                    );
+                    skipped.push(((from, to), reason));
+                }
+            }
+        }
+    }
+
+    selected.sort();
+    selected.dedup();
+
+    if let Some(regions_path) = regions {
+        let mut path_regions: FnvHashMap<usize, Vec<(usize, usize)>> =
+            FnvHashMap::default();
+
+        for (name, start, end) in super::load_bed_regions(regions_path)? {
+            let path_ix = path_data.path_names.iter().position(|n| n == &name);
+            if let Some(path_ix) = path_ix {
+                path_regions.entry(path_ix).or_default().push((start, end));
+            } else {
+                warn!("Region path {} does not exist in graph", name);
+            }
+        }
+
+        selected.retain(|&(from, to)| {
+            let overlaps = path_regions.iter().any(|(&path_ix, intervals)| {
+                bubbles::bubble_path_span(
+                    path_data,
+                    path_indices,
+                    path_ix,
+                    from,
+                    to,
+                )
+                .is_some_and(|(start, end)| {
+                    intervals.iter().any(|&(s, e)| start < e && s < end)
+                })
+            });
+            if !overlaps {
+                skipped.push(((from, to), SkipReason::OutsideRegions));
+            }
+            overlaps
+        });
+    }
+
+    Ok((selected, skipped))
+}
+
 #[allow(dead_code)]
 fn find_representative_paths(
     ultrabubbles: &[(u64, u64)],
@@ -267,3 +468,73 @@ fn find_representative_paths(
 
     representative_paths
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gfa::gfa::Orientation;
+    use structopt::StructOpt;
+
+    #[test]
+    fn split_parts_are_filtered_again() {
+        let segments: Vec<(usize, &str)> =
+            (1..=10).map(|id| (id, "A")).collect();
+        let forward = |ids: &[usize]| -> Vec<(usize, Orientation)> {
+            ids.iter().map(|&id| (id, Orientation::Forward)).collect()
+        };
+        let path_data = variants::path_data_from_steps(
+            &segments,
+            &[
+                ("a", &forward(&[1, 2, 3, 4, 8, 9])),
+                ("b", &forward(&[1, 5, 6, 4, 10, 9])),
+            ],
+        );
+        let nodes: FnvHashSet<u64> = (1..=10).collect();
+        let mut path_indices =
+            variants::bubble_path_indices(&path_data.paths, &nodes);
+
+        let bubbles: Vec<Bubble> = [(1, 9), (3, 8)]
+            .iter()
+            .map(|&(from, to)| Bubble {
+                from,
+                to,
+                info: Some(bubbles::bubble_path_info(
+                    &path_data,
+                    &path_indices,
+                    from,
+                    to,
+                )),
+            })
+            .collect();
+
+        let filter = BubbleFilterArgs::from_iter(&[
+            "test",
+            "--min-nodes",
+            "2",
+            "--max-nodes",
+            "3",
+        ]);
+
+        // (1, 9) splits at 4 into (1, 4), which is still too large,
+        // and (4, 9); (3, 8) is too small, and isn't split
+        let (selected, mut skipped) = select_bubbles(
+            &filter,
+            true,
+            None,
+            &path_data,
+            &mut path_indices,
+            &bubbles,
+        )
+        .unwrap();
+        skipped.sort_by_key(|&(bubble, _)| bubble);
+
+        assert_eq!(selected, vec![(4, 9)]);
+        assert_eq!(
+            skipped,
+            vec![
+                ((1, 4), SkipReason::TooManyNodes),
+                ((3, 8), SkipReason::TooFewNodes)
+            ]
+        );
+    }
+}
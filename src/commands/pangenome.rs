@@ -0,0 +1,144 @@
+use structopt::StructOpt;
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use gfa::gfa::GFA;
+
+use crate::{pangenome::Pangenome, variants};
+
+use super::{load_gfa, Error, Result};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+/// Compute pangenome statistics from the composition of the paths:
+/// the core, shell, and cloud segment counts and sequence totals, and
+/// optionally the number of paths and samples traversing each
+/// segment, the histogram of those sample counts, and the pangenome
+/// growth curve.
+///
+/// Paths are grouped into samples using PanSN names,
+/// `sample#haplotype#contig`, unless --by-path is given.
+#[derive(StructOpt, Debug)]
+pub struct PangenomeArgs {
+    /// Treat each path as its own sample
+    #[structopt(name = "by path", long = "by-path")]
+    by_path: bool,
+    /// The fraction of samples that must traverse a segment for it to
+    /// be core
+    #[structopt(
+        name = "core fraction",
+        long = "core-fraction",
+        default_value = "1.0"
+    )]
+    core_fraction: f64,
+    /// Write the number of paths and samples traversing each segment,
+    /// and the part of the pangenome it's in, to this file
+    #[structopt(name = "segment table file", long = "segments")]
+    segments: Option<PathBuf>,
+    /// Write the number of segments, and their total length,
+    /// traversed by each number of samples to this file
+    #[structopt(name = "histogram file", long = "histogram")]
+    histogram: Option<PathBuf>,
+    /// Write the pangenome growth curve to this file
+    #[structopt(name = "growth curve file", long = "growth")]
+    growth: Option<PathBuf>,
+    /// The number of random sample orders to compute the growth curve
+    /// over
+    #[structopt(
+        name = "permutations",
+        long = "permutations",
+        default_value = "10"
+    )]
+    permutations: usize,
+    /// Seed for the random sample orders
+    #[structopt(name = "seed", long = "seed", default_value = "42")]
+    seed: u64,
+}
+
+pub fn pangenome(gfa_path: &PathBuf, args: &PangenomeArgs) -> Result<()> {
+    if !(0.0..=1.0).contains(&args.core_fraction) {
+        return Err(Error::InvalidArgument(
+            "--core-fraction must be between 0 and 1".to_string(),
+        ));
+    }
+
+    let path_data = {
+        let gfa: GFA<usize, ()> = load_gfa(gfa_path)?;
+        info!("GFA has {} paths", gfa.paths.len());
+        variants::gfa_path_data(gfa)?
+    };
+
+    let pangenome = Pangenome::from_path_data(&path_data, !args.by_path);
+    info!("Found {} samples", pangenome.group_names.len());
+
+    let partition = pangenome.partition(args.core_fraction);
+
+    println!("#part\tsegments\tbp");
+    for (name, (segments, bp)) in [
+        ("core", partition.core),
+        ("shell", partition.shell),
+        ("cloud", partition.cloud),
+        ("uncovered", partition.uncovered),
+    ]
+    .iter()
+    {
+        println!("{}\t{}\t{}", name, segments, bp);
+    }
+
+    if let Some(path) = &args.segments {
+        // The table has both counts, whichever grouping the parts
+        // are based on
+        let other = Pangenome::from_path_data(&path_data, args.by_path);
+        let (by_path, by_sample) = if args.by_path {
+            (&pangenome, &other)
+        } else {
+            (&other, &pangenome)
+        };
+
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "#segment\tpaths\tsamples\tpart")?;
+        for (ix, id) in pangenome.segment_ids.iter().enumerate() {
+            let count = pangenome.segment_counts[ix];
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}",
+                id,
+                by_path.segment_counts[ix],
+                by_sample.segment_counts[ix],
+                pangenome.part(count, args.core_fraction)
+            )?;
+        }
+        out.flush()?;
+    }
+
+    if let Some(path) = &args.histogram {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "#samples\tsegments\tbp")?;
+        for (count, (segments, bp)) in pangenome.histogram().iter().enumerate()
+        {
+            writeln!(out, "{}\t{}\t{}", count, segments, bp)?;
+        }
+        out.flush()?;
+    }
+
+    if let Some(path) = &args.growth {
+        info!(
+            "Computing growth curve over {} permutations",
+            args.permutations
+        );
+        let growth = pangenome.growth(args.permutations, args.seed);
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "#samples\tmean_bp\tmin_bp\tmax_bp")?;
+        for (ix, (mean, min, max)) in growth.iter().enumerate() {
+            writeln!(out, "{}\t{:.1}\t{}\t{}", ix + 1, mean, min, max)?;
+        }
+        out.flush()?;
+    }
+
+    Ok(())
+}
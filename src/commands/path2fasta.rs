@@ -0,0 +1,138 @@
+use structopt::StructOpt;
+
+use bstr::{BString, ByteSlice};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use gfa::gfa::GFA;
+
+use crate::{depth, sequence, variants};
+
+use super::{
+    byte_lines_iter, load_bed_regions, load_gfa, parse_region, Error, Result,
+};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+/// Output the sequences of the paths in the graph as FASTA.
+///
+/// Segments on reverse steps are reverse complemented, and the
+/// overlaps in the P-line CIGARs are removed. By default all paths
+/// are written; regions use 0-based, end-exclusive coordinates along
+/// the spelled path.
+#[derive(StructOpt, Debug)]
+pub struct Path2FastaArgs {
+    /// Only write these paths
+    #[structopt(name = "path names", long = "paths")]
+    paths: Option<Vec<String>>,
+    /// Only write the paths listed in this file, one per line
+    #[structopt(name = "path names file", long = "paths-file")]
+    paths_file: Option<PathBuf>,
+    /// Write these regions, given as `path:start-end`
+    #[structopt(name = "regions", long = "regions")]
+    regions: Option<Vec<String>>,
+    /// Write the regions in this BED file, where the region names are
+    /// path names
+    #[structopt(name = "regions BED file", long = "bed")]
+    bed: Option<PathBuf>,
+    /// Wrap sequence lines at this many bases, or not at all if 0
+    #[structopt(
+        name = "line width",
+        long = "line-width",
+        default_value = "80"
+    )]
+    line_width: usize,
+    /// Write the FASTA to this file instead of stdout
+    #[structopt(name = "output file", short = "o", long = "out")]
+    out: Option<PathBuf>,
+}
+
+type Selection = Vec<(usize, Option<(usize, usize)>)>;
+
+/// The paths and ranges to spell, along with their FASTA headers.
+fn select_paths(
+    path_data: &variants::PathData,
+    args: &Path2FastaArgs,
+) -> Result<(Selection, Vec<BString>)> {
+    let find_path = |name: &str| {
+        depth::path_index(path_data, name).ok_or_else(|| {
+            Error::InvalidGraph(format!("Path {} does not exist", name))
+        })
+    };
+
+    let mut names: Vec<String> = args.paths.clone().unwrap_or_default();
+    if let Some(file) = &args.paths_file {
+        for line in byte_lines_iter(File::open(file)?) {
+            let line = line?;
+            if !line.is_empty() {
+                names.push(line.to_str_lossy().into_owned());
+            }
+        }
+    }
+
+    let mut regions = Vec::new();
+    if let Some(list) = &args.regions {
+        for region in list.iter() {
+            regions.push(parse_region(region)?);
+        }
+    }
+    if let Some(bed) = &args.bed {
+        regions.extend(load_bed_regions(bed)?);
+    }
+
+    let mut selection = Vec::new();
+    let mut headers = Vec::new();
+
+    if names.is_empty() && regions.is_empty() {
+        for (ix, name) in path_data.path_names.iter().enumerate() {
+            selection.push((ix, None));
+            headers.push(name.clone());
+        }
+        return Ok((selection, headers));
+    }
+
+    for name in names {
+        selection.push((find_path(&name)?, None));
+        headers.push(name.into());
+    }
+
+    for (name, start, end) in regions {
+        let ix = find_path(&name.to_str_lossy())?;
+        selection.push((ix, Some((start, end))));
+        headers.push(format!("{}:{}-{}", name, start, end).into());
+    }
+
+    Ok((selection, headers))
+}
+
+pub fn path2fasta(gfa_path: &PathBuf, args: &Path2FastaArgs) -> Result<()> {
+    let (path_data, overlaps) = {
+        let gfa: GFA<usize, ()> = load_gfa(gfa_path)?;
+        let overlaps = sequence::path_overlaps(&gfa);
+        (variants::gfa_path_data(gfa)?, overlaps)
+    };
+
+    let (selection, headers) = select_paths(&path_data, args)?;
+    info!("Spelling {} paths", selection.len());
+
+    let mut out: Box<dyn Write> = match &args.out {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(std::io::stdout())),
+    };
+
+    sequence::write_paths_fasta(
+        &mut out,
+        &path_data,
+        &overlaps,
+        &selection,
+        &headers,
+        args.line_width,
+    )?;
+    out.flush()?;
+
+    Ok(())
+}
@@ -4,12 +4,15 @@ use saboten::{
     cactusgraph::{BridgeForest, CactusGraph, CactusTree},
 };
 
-use bstr::{io::*, ByteSlice};
+use bstr::{io::*, BString};
+use clap::arg_enum;
+use fnv::FnvHashSet;
 use std::{
     fs::File,
-    io::BufReader,
+    io::{BufReader, BufWriter, Write},
     path::{Path, PathBuf},
 };
+use structopt::StructOpt;
 
 use gfa::{
     gfa::GFA,
@@ -19,11 +22,357 @@ use gfa::{
 #[allow(unused_imports)]
 use log::{debug, info, log_enabled, warn};
 
-use super::Result;
+use crate::{
+    bubbles,
+    bubbles::superbubble,
+    bubbles::{Bubble, BubbleContainment, BubbleInfo},
+    variants,
+};
+
+use super::{load_gfa, Error, Result};
+
+arg_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum BubbleFormat {
+        Plain,
+        Tsv,
+        Json,
+        Bed,
+    }
+}
+
+arg_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum BubbleKind {
+        Ultrabubble,
+        Superbubble,
+        Snarl,
+    }
+}
+
+/// Options for choosing the kind of bubbles to find.
+#[derive(StructOpt, Debug)]
+pub struct BubbleKindArgs {
+    /// The kind of bubbles to find, one of ultrabubble, superbubble,
+    /// snarl. All kinds are written in the same format. Snarls are
+    /// reported without nesting.
+    #[structopt(
+        name = "bubble kind",
+        long = "bubble-kind",
+        default_value = "ultrabubble",
+        possible_values = &BubbleKind::variants(),
+        case_insensitive = true
+    )]
+    pub kind: BubbleKind,
+    /// Skip superbubbles containing more than this many oriented
+    /// segments.
+    #[structopt(
+        name = "max superbubble size",
+        long = "max-superbubble-size",
+        default_value = "100000"
+    )]
+    pub max_superbubble_size: usize,
+}
+
+impl Default for BubbleKindArgs {
+    fn default() -> Self {
+        Self {
+            kind: BubbleKind::Ultrabubble,
+            max_superbubble_size: 100_000,
+        }
+    }
+}
+
+/// Find the ultrabubbles, or other kinds of bubbles, in the graph.
+///
+/// By default only the start and end node of each bubble is
+/// printed. The other formats also include the bubble's parent and
+/// nesting depth, its number of internal nodes, the min and max
+/// allele length, whether it is a simple SNP bubble, and how many
+/// paths traverse it.
+#[derive(StructOpt, Debug)]
+pub struct UltrabubblesArgs {
+    #[structopt(flatten)]
+    kind: BubbleKindArgs,
+    /// Output format, one of plain, tsv, json, bed. BED output
+    /// requires a reference path.
+    #[structopt(
+        name = "output format",
+        long = "format",
+        default_value = "plain",
+        possible_values = &BubbleFormat::variants(),
+        case_insensitive = true
+    )]
+    format: BubbleFormat,
+    /// Paths to use as the coordinate systems for BED output. Each
+    /// traversal of a bubble by one of the paths is output as an
+    /// interval on that path.
+    #[structopt(name = "reference paths", long = "ref")]
+    ref_paths: Option<Vec<String>>,
+    /// With BED output, write the bubbles that aren't traversed by
+    /// any of the reference paths to this file, in the TSV format.
+    #[structopt(name = "unplaced bubbles file", long = "unplaced")]
+    unplaced: Option<PathBuf>,
+}
+
+/// Options for filtering ultrabubbles loaded from a file produced by
+/// the `ultrabubbles` command with `--format tsv`.
+#[derive(StructOpt, Debug)]
+pub struct BubbleFilterArgs {
+    /// Skip bubbles nested deeper than this.
+    #[structopt(long = "max-depth")]
+    max_depth: Option<usize>,
+    /// Skip bubbles with fewer internal nodes than this.
+    #[structopt(long = "min-nodes")]
+    min_nodes: Option<usize>,
+    /// Skip bubbles with more internal nodes than this.
+    #[structopt(long = "max-nodes")]
+    max_nodes: Option<usize>,
+    /// Skip bubbles with a longest allele longer than this.
+    #[structopt(long = "max-allele-len")]
+    max_allele_len: Option<usize>,
+    /// Skip bubbles traversed by fewer paths than this.
+    #[structopt(long = "min-paths")]
+    min_paths: Option<usize>,
+    /// Only use simple SNP bubbles.
+    #[structopt(long = "snp-only")]
+    snp_only: bool,
+}
+
+/// The reason a bubble was left out of variant calling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    Depth,
+    /// More internal nodes than `--max-nodes`
+    TooManyNodes,
+    /// Fewer internal nodes than `--min-nodes`
+    TooFewNodes,
+    AlleleLength,
+    Paths,
+    NotSnp,
+    NoSplit,
+    OutsideRegions,
+}
+
+impl SkipReason {
+    /// True if the bubble was skipped for being too large, in which
+    /// case it may be split into smaller bubbles instead.
+    pub fn is_size(&self) -> bool {
+        matches!(self, SkipReason::TooManyNodes | SkipReason::AlleleLength)
+    }
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            SkipReason::Depth => "depth",
+            SkipReason::TooManyNodes => "too_many_nodes",
+            SkipReason::TooFewNodes => "too_few_nodes",
+            SkipReason::AlleleLength => "allele_length",
+            SkipReason::Paths => "paths",
+            SkipReason::NotSnp => "not_snp",
+            SkipReason::NoSplit => "no_split",
+            SkipReason::OutsideRegions => "outside_regions",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+impl BubbleFilterArgs {
+    pub fn is_active(&self) -> bool {
+        self.max_depth.is_some()
+            || self.min_nodes.is_some()
+            || self.max_nodes.is_some()
+            || self.max_allele_len.is_some()
+            || self.min_paths.is_some()
+            || self.snp_only
+    }
+
+    /// True if the filter needs the nesting depth of the bubbles,
+    /// which only bubbles found in the graph or loaded from a TSV
+    /// file have.
+    pub fn uses_depth(&self) -> bool {
+        self.max_depth.is_some()
+    }
+
+    /// Return the reason the bubble with the given properties should
+    /// be skipped, if any.
+    pub fn skip_reason(&self, info: &BubbleInfo) -> Option<SkipReason> {
+        let above = |lim: Option<usize>, x: usize| lim.is_some_and(|l| x > l);
+        let below = |lim: Option<usize>, x: usize| lim.is_some_and(|l| x < l);
+
+        if above(self.max_depth, info.depth) {
+            Some(SkipReason::Depth)
+        } else if below(self.min_nodes, info.internal_nodes) {
+            Some(SkipReason::TooFewNodes)
+        } else if above(self.max_nodes, info.internal_nodes) {
+            Some(SkipReason::TooManyNodes)
+        } else if above(self.max_allele_len, info.max_allele_len) {
+            Some(SkipReason::AlleleLength)
+        } else if below(self.min_paths, info.path_count) {
+            Some(SkipReason::Paths)
+        } else if self.snp_only && !info.is_snp {
+            Some(SkipReason::NotSnp)
+        } else {
+            None
+        }
+    }
+
+    /// Apply the filter to the bubbles, returning the ends of the
+    /// bubbles that were kept. Bubbles without properties are always
+    /// kept.
+    pub fn filter(&self, bubbles: &[Bubble]) -> Vec<(u64, u64)> {
+        if !self.is_active() {
+            return bubbles.iter().map(Bubble::ends).collect();
+        }
+
+        let missing = bubbles.iter().filter(|b| b.info.is_none()).count();
+        if missing > 0 {
+            warn!(
+                "{} bubbles lack the properties needed for filtering, \
+                 use `ultrabubbles --format tsv` to produce them",
+                missing
+            );
+        }
+
+        let kept: Vec<_> = bubbles
+            .iter()
+            .filter(|b| {
+                b.info
+                    .as_ref()
+                    .is_none_or(|i| self.skip_reason(i).is_none())
+            })
+            .map(Bubble::ends)
+            .collect();
+
+        info!(
+            "Bubble filter kept {} of {} bubbles",
+            kept.len(),
+            bubbles.len()
+        );
+
+        kept
+    }
+}
+
+pub fn run_saboten(gfa_path: &PathBuf, args: &UltrabubblesArgs) -> Result<()> {
+    if args.format == BubbleFormat::Bed && args.ref_paths.is_none() {
+        return Err(Error::InvalidArgument(
+            "BED output requires a reference path (--ref)".to_string(),
+        ));
+    }
+
+    if args.format == BubbleFormat::Plain {
+        let contained = find_bubbles(gfa_path, &args.kind)?;
+        let ultrabubbles: Vec<_> = contained.keys().copied().collect();
+        return print_ultrabubbles(ultrabubbles.iter());
+    }
+
+    // The other formats need the paths too, so the whole GFA is
+    // parsed once and used both for the bubbles and the path data
+    let (contained, path_data) = {
+        let gfa: GFA<usize, ()> = load_gfa(gfa_path)?;
+        let contained = find_bubbles_in_gfa(&gfa, &args.kind);
+        (contained, variants::gfa_path_data(gfa)?)
+    };
+
+    info!("Computing properties of {} bubbles", contained.len());
+    let bubble_nodes = bubbles::bubble_node_set(contained.keys().copied());
+    let path_indices =
+        variants::bubble_path_indices(&path_data.paths, &bubble_nodes);
+    let bubbles =
+        bubbles::annotate_bubbles(&path_data, &path_indices, &contained);
+
+    match args.format {
+        BubbleFormat::Tsv => {
+            println!("{}", bubbles::BUBBLE_TSV_HEADER);
+            for bubble in bubbles.iter() {
+                println!("{}", bubbles::bubble_tsv_line(bubble));
+            }
+        }
+        BubbleFormat::Json => {
+            println!("[");
+            for (ix, bubble) in bubbles.iter().enumerate() {
+                let sep = if ix + 1 < bubbles.len() { "," } else { "" };
+                println!("{}{}", bubbles::bubble_json(bubble), sep);
+            }
+            println!("]");
+        }
+        BubbleFormat::Bed => {
+            let ref_names = args.ref_paths.as_ref().unwrap();
+            print_bubbles_bed(
+                &path_data,
+                ref_names,
+                &bubbles,
+                args.unplaced.as_ref(),
+            )?;
+        }
+        BubbleFormat::Plain => unreachable!(),
+    }
+
+    Ok(())
+}
 
-pub fn run_saboten(gfa_path: &PathBuf) -> Result<()> {
-    let ultrabubbles = find_ultrabubbles(gfa_path)?;
-    print_ultrabubbles(ultrabubbles.iter())
+/// Print each traversal of a bubble by one of the reference paths as
+/// a BED interval spanning the bubble's start and end nodes. The
+/// bubbles that aren't traversed by any reference path are written to
+/// `unplaced_path`, if given.
+fn print_bubbles_bed(
+    path_data: &variants::PathData,
+    ref_names: &[String],
+    bubbles: &[Bubble],
+    unplaced_path: Option<&PathBuf>,
+) -> Result<()> {
+    let mut ref_ixs = Vec::with_capacity(ref_names.len());
+    for name in ref_names.iter() {
+        let name = BString::from(name.as_str());
+        let ref_ix = path_data
+            .path_names
+            .iter()
+            .position(|n| n == &name)
+            .ok_or_else(|| {
+                Error::InvalidGraph(format!(
+                    "Reference path {} does not exist",
+                    name
+                ))
+            })?;
+        ref_ixs.push(ref_ix);
+    }
+
+    let ends: Vec<_> = bubbles.iter().map(Bubble::ends).collect();
+    let (intervals, unplaced) =
+        bubbles::project_bubbles(path_data, &ref_ixs, &ends);
+
+    for intv in intervals {
+        let strand = if intv.reverse { "-" } else { "+" };
+        println!(
+            "{}\t{}\t{}\t{}-{}\t0\t{}",
+            path_data.path_names[intv.path_ix],
+            intv.start,
+            intv.end,
+            intv.from,
+            intv.to,
+            strand
+        );
+    }
+
+    info!(
+        "{} bubbles are not traversed by the reference paths",
+        unplaced.len()
+    );
+
+    if let Some(unplaced_path) = unplaced_path {
+        let unplaced: FnvHashSet<(u64, u64)> = unplaced.into_iter().collect();
+        let mut out = BufWriter::new(File::create(unplaced_path)?);
+        writeln!(out, "{}", bubbles::BUBBLE_TSV_HEADER)?;
+        for bubble in bubbles {
+            if unplaced.contains(&bubble.ends()) {
+                writeln!(out, "{}", bubbles::bubble_tsv_line(bubble))?;
+            }
+        }
+    }
+
+    Ok(())
 }
 
 pub fn print_ultrabubbles<'a, I>(ultrabubbles: I) -> Result<()>
@@ -37,17 +386,52 @@ where
     Ok(())
 }
 
-pub fn find_ultrabubbles(gfa_path: &PathBuf) -> Result<Vec<(u64, u64)>> {
+/// Find the bubbles of the given kind in the graph, along with the
+/// bubbles each of them contains.
+pub fn find_bubbles(
+    gfa_path: &PathBuf,
+    kind_args: &BubbleKindArgs,
+) -> Result<BubbleContainment> {
     let mut parser_builder = GFAParserBuilder::all();
     parser_builder.paths = false;
     parser_builder.containments = false;
     let parser: GFAParser<usize, ()> = parser_builder.build();
     let gfa: GFA<usize, ()> = parser.parse_file(gfa_path)?;
 
-    info!("Computing ultrabubbles");
+    Ok(find_bubbles_in_gfa(&gfa, kind_args))
+}
+
+/// Find the bubbles of the given kind in an already loaded graph. Only
+/// the segments and links of the GFA are used, so the same GFA can be
+/// used to build the path data afterward.
+pub fn find_bubbles_in_gfa(
+    gfa: &GFA<usize, ()>,
+    kind_args: &BubbleKindArgs,
+) -> BubbleContainment {
+    let bubbles = match kind_args.kind {
+        BubbleKind::Ultrabubble => cactus_bubbles(gfa, false),
+        BubbleKind::Snarl => cactus_bubbles(gfa, true),
+        BubbleKind::Superbubble => {
+            superbubble::find_superbubbles(gfa, kind_args.max_superbubble_size)
+        }
+    };
+
+    info!("Found {} {}s", bubbles.len(), kind_args.kind);
+
+    bubbles
+}
+
+/// Find the ultrabubbles in the graph using saboten's cactus graph
+/// decomposition, or, if `snarls` is true, all the chain pairs and
+/// bridge pairs, i.e. the snarls. Nesting isn't computed for snarls.
+fn cactus_bubbles(gfa: &GFA<usize, ()>, snarls: bool) -> BubbleContainment {
+    info!(
+        "Computing {}",
+        if snarls { "snarls" } else { "ultrabubbles" }
+    );
     debug!("Building biedged graph");
 
-    let be_graph = BiedgedGraph::from_gfa(&gfa);
+    let be_graph = BiedgedGraph::from_gfa(gfa);
 
     debug!("Building cactus graph");
     let cactus_graph = CactusGraph::from_biedged_graph(&be_graph);
@@ -58,6 +442,29 @@ pub fn find_ultrabubbles(gfa_path: &PathBuf) -> Result<Vec<(u64, u64)>> {
     debug!("Building bridge forest");
     let bridge_forest = BridgeForest::from_cactus_graph(&cactus_graph);
 
+    if snarls {
+        debug!("Finding chain pairs and bridge pairs");
+        let chain_pairs = cactus_tree.find_chain_pairs();
+        let bridge_pairs = bridge_forest.find_bridge_pairs();
+        debug!(
+            "Found {} chain pairs and {} bridge pairs",
+            chain_pairs.len(),
+            bridge_pairs.len()
+        );
+
+        let snarls: BubbleContainment = chain_pairs
+            .into_iter()
+            .map(|p| (p.x, p.y))
+            .chain(bridge_pairs.into_iter().map(|p| (p.x, p.y)))
+            .map(|x_y| (x_y, Vec::new()))
+            .collect();
+
+        return cactusgraph::inverse_map_ultrabubbles(snarls)
+            .into_iter()
+            .filter(|((x, y), _)| x != y)
+            .collect();
+    }
+
     debug!("Finding ultrabubbles");
     let ultrabubbles =
         cactusgraph::find_ultrabubbles(&cactus_tree, &bridge_forest);
@@ -65,30 +472,44 @@ pub fn find_ultrabubbles(gfa_path: &PathBuf) -> Result<Vec<(u64, u64)>> {
     let ultrabubbles = cactusgraph::inverse_map_ultrabubbles(ultrabubbles);
 
     debug!("Done computing ultrabubbles");
-    Ok(ultrabubbles.into_iter().map(|(x_y, _cont)| x_y).collect())
+    ultrabubbles.into_iter().collect()
 }
 
-static LINE_ERROR: &str = "Ultrabubble record was missing fields";
+/// Find the ultrabubbles in the graph, along with the ultrabubbles
+/// each of them contains.
+pub fn find_ultrabubbles_nested(
+    gfa_path: &PathBuf,
+) -> Result<BubbleContainment> {
+    find_bubbles(gfa_path, &BubbleKindArgs::default())
+}
 
-pub fn load_ultrabubbles<P: AsRef<Path>>(path: P) -> Result<Vec<(u64, u64)>> {
+pub fn find_ultrabubbles(gfa_path: &PathBuf) -> Result<Vec<(u64, u64)>> {
+    let ultrabubbles = find_ultrabubbles_nested(gfa_path)?;
+    Ok(ultrabubbles.into_keys().collect())
+}
+
+/// Load bubbles from a file, in either the plain or the TSV format
+/// produced by the `ultrabubbles` command.
+pub fn load_bubbles<P: AsRef<Path>>(path: P) -> Result<Vec<Bubble>> {
     info!("Loading ultrabubbles from file {}", path.as_ref().display());
     let file = File::open(path.as_ref())?;
     let reader = BufReader::new(file);
     let lines = reader.byte_lines();
 
-    let mut ultrabubbles = Vec::new();
+    let mut bubbles = Vec::new();
 
     for line in lines {
         let line = line?;
-        let mut fields = line.split_str("\t");
-        let start = fields.next().ok_or(LINE_ERROR)?.to_str()?;
-        let start = start.parse::<u64>()?;
-
-        let end = fields.next().ok_or(LINE_ERROR)?.to_str()?;
-        let end = end.parse::<u64>()?;
-
-        ultrabubbles.push((start, end));
+        if line.is_empty() || line.starts_with(b"#") {
+            continue;
+        }
+        bubbles.push(bubbles::parse_bubble_line(&line)?);
     }
 
-    Ok(ultrabubbles)
+    Ok(bubbles)
+}
+
+pub fn load_ultrabubbles<P: AsRef<Path>>(path: P) -> Result<Vec<(u64, u64)>> {
+    let bubbles = load_bubbles(path)?;
+    Ok(bubbles.iter().map(Bubble::ends).collect())
 }
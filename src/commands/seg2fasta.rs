@@ -0,0 +1,81 @@
+use structopt::StructOpt;
+
+use bstr::BString;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use gfa::{gfa::GFA, optfields::OptionalFields};
+
+use crate::sequence;
+
+use super::{load_gfa, Result};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+/// Output the sequences of the segments in the graph as FASTA.
+///
+/// Segments without a sequence are skipped.
+#[derive(StructOpt, Debug)]
+pub struct Seg2FastaArgs {
+    /// Add the segment length to each header, as an `LN:i` tag
+    #[structopt(name = "include length", long = "length")]
+    length: bool,
+    /// Add these tags of the segments to the headers, if present,
+    /// e.g. `--tags RC DP`
+    #[structopt(name = "tags", long = "tags")]
+    tags: Option<Vec<String>>,
+    /// Wrap sequence lines at this many bases, or not at all if 0
+    #[structopt(
+        name = "line width",
+        long = "line-width",
+        default_value = "80"
+    )]
+    line_width: usize,
+    /// Write the FASTA to this file instead of stdout
+    #[structopt(name = "output file", short = "o", long = "out")]
+    out: Option<PathBuf>,
+}
+
+pub fn seg2fasta(gfa_path: &PathBuf, args: &Seg2FastaArgs) -> Result<()> {
+    let gfa: GFA<BString, OptionalFields> = load_gfa(gfa_path)?;
+
+    let tags: Vec<Vec<u8>> = args
+        .tags
+        .iter()
+        .flatten()
+        .map(|t| t.bytes().collect())
+        .collect();
+
+    let mut out: Box<dyn Write> = match &args.out {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(std::io::stdout())),
+    };
+
+    let mut skipped = 0;
+
+    for segment in gfa.segments.iter() {
+        if segment.sequence.as_slice() == b"*" {
+            skipped += 1;
+            continue;
+        }
+        let header =
+            sequence::segment_fasta_header(segment, args.length, &tags);
+        sequence::write_fasta(
+            &mut out,
+            &header,
+            &segment.sequence,
+            args.line_width,
+        )?;
+    }
+    out.flush()?;
+
+    if skipped > 0 {
+        warn!("Skipped {} segments without a sequence", skipped);
+    }
+
+    Ok(())
+}
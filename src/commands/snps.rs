@@ -16,7 +16,7 @@ use crate::{
     variants::{PathStep, SNPRow},
 };
 
-use super::{load_gfa, Result};
+use super::{load_gfa, Error, Result};
 
 /// Given a reference path from the GFA, by name, find and report the
 /// SNPs for all other paths compared to the reference.
@@ -49,6 +49,8 @@ pub struct SNPArgs {
         required_unless_one(&["SNP positions", "SNP positions file"])
     )]
     ultrabubbles_file: Option<PathBuf>,
+    #[structopt(flatten)]
+    bubble_filter: super::saboten::BubbleFilterArgs,
 }
 
 fn snp_positions(args: &SNPArgs) -> Result<Vec<usize>> {
@@ -64,7 +66,9 @@ fn snp_positions(args: &SNPArgs) -> Result<Vec<usize>> {
     }
 
     if res.is_empty() {
-        panic!("No SNPs were provided");
+        return Err(Error::InvalidArgument(
+            "No SNPs were provided".to_string(),
+        ));
     }
 
     Ok(res)
@@ -121,12 +125,14 @@ pub fn gfa2snps(gfa_path: &PathBuf, args: SNPArgs) -> Result<()> {
         let gfa: GFA<usize, ()> = load_gfa(&gfa_path)?;
 
         if gfa.paths.len() < 2 {
-            panic!("GFA must contain at least two paths");
+            return Err(Error::InvalidGraph(
+                "GFA must contain at least two paths".to_string(),
+            ));
         }
 
         info!("GFA has {} paths", gfa.paths.len());
 
-        variants::gfa_path_data(gfa)
+        variants::gfa_path_data(gfa)?
     };
 
     info!("Using reference path: {}", ref_path_name);
@@ -135,17 +141,26 @@ pub fn gfa2snps(gfa_path: &PathBuf, args: SNPArgs) -> Result<()> {
         .path_names
         .iter()
         .position(|name| name == &ref_path_name)
-        .expect("Reference path does not exist in graph");
+        .ok_or_else(|| {
+            Error::InvalidGraph(format!(
+                "Reference path {} does not exist in graph",
+                ref_path_name
+            ))
+        })?;
 
     let ref_path = &path_data.paths[ref_path_ix];
 
     let ultrabubbles = if let Ok(mut positions) = snp_positions(&args) {
-        Ok(build_snp_reference_bubbles(&ref_path, &mut positions))
+        build_snp_reference_bubbles(ref_path, &mut positions)
     } else if let Some(path) = &args.ultrabubbles_file {
-        super::saboten::load_ultrabubbles(path)
+        let bubbles = super::saboten::load_bubbles(path)?;
+        args.bubble_filter.filter(&bubbles)
     } else {
-        unreachable!()
-    }?;
+        return Err(Error::InvalidArgument(
+            "Either SNP positions or an ultrabubbles file is required"
+                .to_string(),
+        ));
+    };
 
     info!("Found ultrabubbles for {} SNPs", ultrabubbles.len());
 
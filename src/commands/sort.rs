@@ -0,0 +1,106 @@
+use structopt::StructOpt;
+
+use bstr::BString;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use gfa::{
+    gfa::{name_conversion::NameMap, GFA},
+    optfields::OptionalFields,
+};
+
+use crate::{sort, util};
+
+use super::{convert_names::gfa_to_name_map_path, load_gfa, Error, Result};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+/// Sort the segments of the graph in topological order, or in the
+/// order of a reference path, and optionally rename them to
+/// consecutive integers.
+///
+/// With a reference path, the segments not on the path are placed
+/// after it in topological order. When renaming, the name map is
+/// saved as JSON, so the GFA can be converted back with `id-convert`.
+#[derive(StructOpt, Debug)]
+pub struct SortArgs {
+    /// Sort the segments in the order this path first visits them,
+    /// instead of topologically
+    #[structopt(name = "reference path", long = "ref")]
+    ref_path: Option<String>,
+    /// Rename the segments to consecutive integers in the sorted order
+    #[structopt(name = "convert to integer names", long = "to-int")]
+    to_usize: bool,
+    /// Where to save the name map when renaming. Defaults to
+    /// `<output>.name_map.json`
+    #[structopt(
+        name = "path to name map",
+        long = "namemap",
+        parse(from_os_str)
+    )]
+    name_map_path: Option<PathBuf>,
+    /// Write the sorted GFA to this file instead of stdout
+    #[structopt(name = "output file", short = "o", long = "out")]
+    out: Option<PathBuf>,
+}
+
+pub fn sort(gfa_path: &PathBuf, args: &SortArgs) -> Result<()> {
+    let name_map_path = if !args.to_usize {
+        None
+    } else if let Some(path) = &args.name_map_path {
+        Some(path.clone())
+    } else if let Some(out) = &args.out {
+        Some(gfa_to_name_map_path(out)?)
+    } else {
+        return Err(Error::InvalidArgument(
+            "--to-int needs --namemap or --out, to know where to save the \
+             name map"
+                .to_string(),
+        ));
+    };
+
+    let mut gfa: GFA<BString, OptionalFields> = load_gfa(gfa_path)?;
+
+    let order = match &args.ref_path {
+        Some(ref_path) => sort::path_order(&gfa, ref_path.as_bytes())
+            .ok_or_else(|| {
+                Error::InvalidGraph(format!(
+                    "Reference path {} does not exist in graph",
+                    ref_path
+                ))
+            })?,
+        None => sort::topological_order(&gfa),
+    };
+
+    sort::apply_order(&mut gfa, &order);
+    info!("Sorted {} segments", gfa.segments.len());
+
+    let mut out: Box<dyn Write> = match &args.out {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(std::io::stdout())),
+    };
+
+    if let Some(name_map_path) = name_map_path {
+        // The name map numbers the segments in the order they appear,
+        // which is now the sorted order
+        let name_map = NameMap::build_from_gfa(&gfa);
+        let renamed =
+            name_map.gfa_bstring_to_usize(&gfa, false).ok_or_else(|| {
+                Error::InvalidGraph(
+                    "Could not convert the GFA segment IDs".to_string(),
+                )
+            })?;
+        name_map.save_json(&name_map_path)?;
+        info!("Saved name map to {}", name_map_path.display());
+        util::write_gfa_io(&renamed, &mut out)?;
+    } else {
+        util::write_gfa_io(&gfa, &mut out)?;
+    }
+    out.flush()?;
+
+    Ok(())
+}
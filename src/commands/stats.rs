@@ -1,22 +1,77 @@
-use std::path::PathBuf;
+use clap::arg_enum;
+use structopt::StructOpt;
 
-use gfa::gfa::GFA;
+use bstr::BString;
+use std::path::PathBuf;
 
-use handlegraph::hashgraph::HashGraph;
+use gfa::{gfa::GFA, optfields::OptionalFields};
 
-use crate::edges;
+use crate::{edges, stats};
 
 use super::{load_gfa, Result};
 
+/// Print the number of links attached to the start and end of each
+/// segment, along with the number of self-loops, and of links that
+/// reverse the orientation.
 pub fn edge_count(gfa_path: &PathBuf) -> Result<()> {
-    let gfa: GFA<usize, ()> = load_gfa(gfa_path)?;
+    let gfa: GFA<BString, ()> = load_gfa(gfa_path)?;
 
-    let hashgraph = HashGraph::from_gfa(&gfa);
-    let edge_counts = edges::graph_edge_count(&hashgraph);
-    println!("nodeid,inbound,outbound,total");
-    edge_counts
+    let edge_counts = edges::segment_edges(&gfa);
+    println!("segment,start,end,total,self_loops,reversing");
+    gfa.segments
         .iter()
-        .for_each(|(id, i, o, t)| println!("{},{},{},{}", id, i, o, t));
+        .zip(edge_counts.iter())
+        .for_each(|(seg, e)| {
+            println!(
+                "{},{},{},{},{},{}",
+                seg.name,
+                e.start,
+                e.end,
+                e.total(),
+                e.self_loops,
+                e.reversing
+            )
+        });
+
+    Ok(())
+}
+
+arg_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum StatsFormat {
+        Text,
+        Tsv,
+        Json,
+    }
+}
+
+/// Print summary statistics of the graph: segment, link, and path
+/// counts, sequence length and N50, degree distribution, tips, dead
+/// ends, self-loops, connected components, path lengths, and how much
+/// of the graph the paths cover.
+#[derive(StructOpt, Debug)]
+pub struct StatsArgs {
+    /// Output format, one of text, tsv, json
+    #[structopt(
+        name = "output format",
+        long = "format",
+        default_value = "text",
+        possible_values = &StatsFormat::variants(),
+        case_insensitive = true
+    )]
+    format: StatsFormat,
+}
+
+pub fn graph_stats(gfa_path: &PathBuf, args: &StatsArgs) -> Result<()> {
+    let gfa: GFA<BString, OptionalFields> = load_gfa(gfa_path)?;
+
+    let stats = stats::graph_stats(&gfa);
+
+    match args.format {
+        StatsFormat::Text => print!("{}", stats.to_text()),
+        StatsFormat::Tsv => print!("{}", stats.to_tsv()),
+        StatsFormat::Json => println!("{}", stats.to_json()),
+    }
 
     Ok(())
 }
@@ -2,13 +2,24 @@ use clap::arg_enum;
 use structopt::{clap::ArgGroup, StructOpt};
 
 use bstr::{BString, ByteSlice, ByteVec};
-use std::{fs::File, path::PathBuf};
+use regex::bytes::Regex;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
 
 use gfa::{gfa::GFA, optfields::OptionalFields, writer::gfa_string};
 
-use crate::subgraph;
+use crate::subgraph::{
+    self,
+    streaming::{LineFilter, Selection},
+    PathFilter,
+};
 
-use super::{byte_lines_iter, load_gfa, Result};
+use super::{
+    byte_lines_iter, load_bed_regions, load_gfa, parse_region, Error, Result,
+};
 
 #[allow(unused_imports)]
 use log::{debug, info, warn};
@@ -18,19 +29,31 @@ arg_enum! {
     pub enum SubgraphBy {
         Paths,
         Segments,
+        Regions,
+        Core,
+        Private,
+        Difference,
     }
 }
 
 /// Generate a subgraph of the input GFA.
 ///
 /// The output will be the lines of the input GFA that include the
-/// provided segment or path names.
+/// provided segment or path names, or the parts of the graph covering
+/// the provided path regions.
 #[derive(StructOpt, Debug)]
-#[structopt(group = ArgGroup::with_name("names").required(true))]
+#[structopt(group = ArgGroup::with_name("names").required(true).multiple(true))]
 pub struct SubgraphArgs {
-    /// Choose between providing a list of path names, or a list of
-    /// components of segment names
-    #[structopt(name = "paths|segments", possible_values = &["paths", "segments"], case_insensitive = true)]
+    /// Choose between providing a list of path names, a list of
+    /// components of segment names, or a list of path regions. Regions
+    /// are given as `path:start-end` with --names, or as a BED file
+    /// with --file, using 0-based, end-exclusive coordinates.
+    ///
+    /// The core, private, and difference modes choose segments by the
+    /// given paths: the segments visited by all of them, the segments
+    /// visited by only one path, or the segments visited by the given
+    /// paths but not by the paths given with --minus
+    #[structopt(name = "paths|segments|regions|core|private|difference", possible_values = &["paths", "segments", "regions", "core", "private", "difference"], case_insensitive = true)]
     subgraph_by: SubgraphBy,
     /// File containing a list of names
     #[structopt(
@@ -40,40 +63,317 @@ pub struct SubgraphArgs {
     )]
     file: Option<PathBuf>,
     /// Provide a list of names on the command line
-    #[structopt(name = "List of names", long = "names", group = "names")]
+    #[structopt(
+        name = "List of names",
+        long = "names",
+        group = "names",
+        conflicts_with = "File containing names"
+    )]
     list: Option<Vec<String>>,
+    /// Choose the paths whose names match any of these regular
+    /// expressions
+    #[structopt(name = "path regex", long = "regex", group = "names")]
+    regex: Option<Vec<String>>,
+    /// Choose the paths whose names match any of these globs, where
+    /// `*` matches any number of characters, and `?` matches one
+    #[structopt(name = "path glob", long = "glob", group = "names")]
+    glob: Option<Vec<String>>,
+    /// Only choose the paths of this sample, using PanSN path names,
+    /// `sample#haplotype#contig`
+    #[structopt(name = "PanSN sample", long = "sample", group = "names")]
+    sample: Option<String>,
+    /// Only choose the paths of this haplotype, using PanSN path names
+    #[structopt(name = "PanSN haplotype", long = "haplotype", group = "names")]
+    haplotype: Option<String>,
+    /// Remove the chosen paths, and the segments only they visit,
+    /// instead of keeping them
+    #[structopt(name = "exclude", long = "exclude")]
+    exclude: bool,
+    /// In difference mode, the paths whose segments are removed
+    #[structopt(name = "minus paths", long = "minus")]
+    minus: Option<Vec<String>>,
+    /// Print the names of the chosen segments, one per line, instead
+    /// of a GFA
+    #[structopt(name = "segment list", long = "segment-list")]
+    segment_list: bool,
+    /// When extracting segments, also include the segments up to this
+    /// many links away from the given segments
+    #[structopt(name = "context steps", long = "context", short = "c")]
+    context_steps: Option<usize>,
+    /// When extracting segments, also include the segments within
+    /// this many base pairs of the given segments
+    #[structopt(
+        name = "context bases",
+        long = "context-bp",
+        conflicts_with = "context steps"
+    )]
+    context_bases: Option<usize>,
+    /// Read the GFA line by line, in two or three passes, instead of
+    /// loading it into memory, and write the subgraph as it's read.
+    /// Only supported for paths and segments, without context
+    #[structopt(name = "streaming", long = "streaming")]
+    streaming: bool,
+    /// Write the subgraph to this file instead of stdout
+    #[structopt(name = "output file", long = "out", short = "o")]
+    out: Option<PathBuf>,
+    /// Write the kept lines in the order they appear in the input,
+    /// including comments and unknown record types, instead of
+    /// writing segments, links, paths, and containments in turn. Not
+    /// supported for regions
+    #[structopt(name = "keep order", long = "keep-order")]
+    keep_order: bool,
+}
+
+impl SubgraphArgs {
+    /// The set operation for the path membership modes, if used.
+    fn set_operation(&self) -> Option<subgraph::SetOperation> {
+        use subgraph::SetOperation;
+        match self.subgraph_by {
+            SubgraphBy::Core => Some(SetOperation::Core),
+            SubgraphBy::Private => Some(SetOperation::Private),
+            SubgraphBy::Difference => {
+                let minus: Vec<Vec<u8>> = self
+                    .minus
+                    .iter()
+                    .flatten()
+                    .map(|s| s.bytes().collect())
+                    .collect();
+                Some(SetOperation::Difference(PathFilter::from_names(&minus)))
+            }
+            _ => None,
+        }
+    }
+
+    fn has_path_patterns(&self) -> bool {
+        self.regex.is_some()
+            || self.glob.is_some()
+            || self.sample.is_some()
+            || self.haplotype.is_some()
+    }
+
+    /// Build the path filter from the names, and the path selection
+    /// options.
+    fn path_filter(&self, names: &[Vec<u8>]) -> Result<PathFilter> {
+        let mut filter = PathFilter::from_names(names);
+
+        for re in self.regex.iter().flatten() {
+            filter.patterns.push(Regex::new(re)?);
+        }
+
+        for glob in self.glob.iter().flatten() {
+            filter.patterns.push(subgraph::glob_regex(glob)?);
+        }
+
+        filter.sample = self.sample.as_ref().map(|s| s.bytes().collect());
+        filter.haplotype = self.haplotype.as_ref().map(|h| h.bytes().collect());
+
+        Ok(filter)
+    }
+
+    fn context(&self) -> Option<subgraph::Context> {
+        self.context_steps
+            .map(subgraph::Context::Steps)
+            .or_else(|| self.context_bases.map(subgraph::Context::Bases))
+    }
 }
 
 pub fn subgraph(gfa_path: &PathBuf, args: &SubgraphArgs) -> Result<()> {
+    let context = args.context();
+    if context.is_some() && args.subgraph_by != SubgraphBy::Segments {
+        return Err(Error::InvalidArgument(
+            "Context can only be used when extracting segments".to_string(),
+        ));
+    }
+
+    let set_operation = args.set_operation();
+
+    if args.has_path_patterns()
+        && args.subgraph_by != SubgraphBy::Paths
+        && set_operation.is_none()
+    {
+        return Err(Error::InvalidArgument(
+            "Path patterns can only be used when choosing paths".to_string(),
+        ));
+    }
+
+    if args.exclude && args.subgraph_by != SubgraphBy::Paths {
+        return Err(Error::InvalidArgument(
+            "--exclude can only be used with paths".to_string(),
+        ));
+    }
+
+    if args.subgraph_by == SubgraphBy::Difference && args.minus.is_none() {
+        return Err(Error::InvalidArgument(
+            "Difference requires the paths to remove (--minus)".to_string(),
+        ));
+    }
+
+    if args.subgraph_by != SubgraphBy::Difference && args.minus.is_some() {
+        return Err(Error::InvalidArgument(
+            "--minus can only be used with difference".to_string(),
+        ));
+    }
+
+    if args.segment_list && (args.keep_order || args.streaming) {
+        return Err(Error::InvalidArgument(
+            "--segment-list can't be used with --keep-order or --streaming"
+                .to_string(),
+        ));
+    }
+
+    if args.keep_order && args.subgraph_by == SubgraphBy::Regions {
+        return Err(Error::InvalidArgument(
+            "--keep-order can't be used with regions".to_string(),
+        ));
+    }
+
+    let mut out: Box<dyn Write> = if let Some(path) = &args.out {
+        Box::new(BufWriter::new(File::create(path)?))
+    } else {
+        Box::new(BufWriter::new(std::io::stdout()))
+    };
+
+    if args.streaming {
+        streaming_subgraph(gfa_path, args, &mut out)?;
+        out.flush()?;
+        return Ok(());
+    }
+
     let gfa: GFA<BString, OptionalFields> = load_gfa(gfa_path)?;
 
-    let names: Vec<Vec<u8>> = if let Some(list) = &args.list {
-        list.iter().map(|s| s.bytes().collect()).collect()
+    let new_gfa = if args.subgraph_by == SubgraphBy::Regions {
+        let regions = if let Some(list) = &args.list {
+            list.iter()
+                .map(|r| parse_region(r))
+                .collect::<Result<Vec<_>>>()?
+        } else {
+            load_bed_regions(args.file.as_ref().unwrap())?
+        };
+        subgraph::region_subgraph(&gfa, &regions)
+    } else {
+        let names = read_names(args)?;
+        match args.subgraph_by {
+            SubgraphBy::Paths => {
+                let filter = args.path_filter(&names)?;
+                subgraph::path_filter_subgraph(&gfa, &filter, args.exclude)
+            }
+            SubgraphBy::Segments => {
+                let names = if let Some(context) = context {
+                    subgraph::segment_neighborhood(&gfa, &names, context)
+                } else {
+                    names
+                };
+                subgraph::segments_subgraph(&gfa, &names)
+            }
+            SubgraphBy::Core | SubgraphBy::Private | SubgraphBy::Difference => {
+                let filter = args.path_filter(&names)?;
+                let operation = set_operation.as_ref().unwrap();
+                let segments =
+                    subgraph::path_set_segments(&gfa, &filter, operation);
+                info!("Chose {} segments", segments.len());
+                subgraph::segments_subgraph(&gfa, &segments)
+            }
+            SubgraphBy::Regions => unreachable!(),
+        }
+    };
+
+    if args.segment_list {
+        for seg in new_gfa.segments.iter() {
+            writeln!(out, "{}", seg.name)?;
+        }
+        out.flush()?;
+        return Ok(());
+    }
+
+    if args.keep_order {
+        // Use the subgraph's segments to pick the lines from the
+        // input, so they're written in their original order
+        let segments = new_gfa.segments.iter().map(|s| s.name.clone());
+        let filter = if args.subgraph_by == SubgraphBy::Paths {
+            let paths: Vec<Vec<u8>> =
+                new_gfa.paths.iter().map(|p| p.path_name.to_vec()).collect();
+            let paths = PathFilter::from_names(&paths);
+            LineFilter::from_paths(segments.collect(), paths)
+        } else {
+            let lengths = gfa
+                .segments
+                .iter()
+                .map(|s| (s.name.clone(), subgraph::segment_length(s)))
+                .collect();
+            LineFilter::from_segments(segments.collect(), lengths)
+        };
+        let written = filter.write_subgraph(gfa_path, &mut out)?;
+        info!("Wrote {} lines", written);
+    } else {
+        writeln!(out, "{}", gfa_string(&new_gfa))?;
+    }
+
+    out.flush()?;
+
+    Ok(())
+}
+
+/// Read the segment or path names from the command line, or from the
+/// file or stdin.
+fn read_names(args: &SubgraphArgs) -> Result<Vec<Vec<u8>>> {
+    if let Some(list) = &args.list {
+        Ok(list.iter().map(|s| s.bytes().collect()).collect())
+    } else if args.file.is_none() && args.has_path_patterns() {
+        Ok(Vec::new())
     } else {
         let in_lines = if let Some(path) = &args.file {
-            byte_lines_iter(File::open(path).unwrap())
+            byte_lines_iter(File::open(path)?)
         } else {
             byte_lines_iter(std::io::stdin())
         };
 
-        if args.subgraph_by == SubgraphBy::Segments {
-            in_lines
-                .flat_map(|line| {
-                    line.split_str("\t")
-                        .map(Vec::from_slice)
-                        .collect::<Vec<_>>()
-                })
-                .collect()
-        } else {
-            in_lines.collect()
+        let mut names = Vec::new();
+        for line in in_lines {
+            let line = line?;
+            if args.subgraph_by == SubgraphBy::Segments {
+                names.extend(line.split_str("\t").map(Vec::from_slice));
+            } else {
+                names.push(line);
+            }
         }
-    };
+        Ok(names)
+    }
+}
 
-    let new_gfa = match args.subgraph_by {
-        SubgraphBy::Paths => subgraph::paths_new_subgraph(&gfa, &names),
-        SubgraphBy::Segments => subgraph::segments_subgraph(&gfa, &names),
+fn streaming_subgraph<W: Write>(
+    gfa_path: &PathBuf,
+    args: &SubgraphArgs,
+    out: &mut W,
+) -> Result<()> {
+    if args.context().is_some() {
+        return Err(Error::InvalidArgument(
+            "Context can't be used with --streaming".to_string(),
+        ));
+    }
+
+    if args.exclude {
+        return Err(Error::InvalidArgument(
+            "--exclude can't be used with --streaming".to_string(),
+        ));
+    }
+
+    let names = read_names(args)?;
+    let selection = match args.subgraph_by {
+        SubgraphBy::Paths => Selection::Paths(args.path_filter(&names)?),
+        SubgraphBy::Segments => Selection::Segments(names),
+        _ => {
+            return Err(Error::InvalidArgument(format!(
+                "{} can't be used with --streaming",
+                args.subgraph_by
+            )))
+        }
     };
-    println!("{}", gfa_string(&new_gfa));
+
+    let filter = LineFilter::scan(gfa_path, &selection)?;
+
+    let written = filter.write_subgraph(gfa_path, out)?;
+
+    info!("Wrote {} lines", written);
 
     Ok(())
 }
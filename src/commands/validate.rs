@@ -0,0 +1,43 @@
+use std::{collections::BTreeMap, fs::File, io::BufReader, path::PathBuf};
+
+use crate::validate;
+
+use super::{Error, Result};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+/// Check the input GFA for problems, and print a report with the line
+/// number of each one. Exits with an error if any problems are found.
+///
+/// Checks for duplicate segment names, LN tags disagreeing with the
+/// sequence length, sequences with characters other than ACGTN,
+/// links, containments, paths, and walks using missing segments,
+/// path steps without a corresponding link, and path overlaps that
+/// disagree with the links.
+pub fn validate(gfa_path: &PathBuf) -> Result<()> {
+    info!("Validating GFA {}", gfa_path.display());
+    let reader = BufReader::new(File::open(gfa_path)?);
+    let issues = validate::validate_gfa(reader)?;
+
+    if issues.is_empty() {
+        info!("No problems found");
+        return Ok(());
+    }
+
+    println!("#line\tkind\tmessage");
+    let mut counts: BTreeMap<validate::IssueKind, usize> = BTreeMap::new();
+    for issue in issues.iter() {
+        println!("{}\t{}\t{}", issue.line, issue.kind, issue.message);
+        *counts.entry(issue.kind).or_default() += 1;
+    }
+
+    for (kind, count) in counts {
+        warn!("{}: {}", kind, count);
+    }
+
+    Err(Error::InvalidGraph(format!(
+        "Found {} problems in the GFA",
+        issues.len()
+    )))
+}
@@ -0,0 +1,171 @@
+use bstr::BString;
+use fnv::{FnvHashMap, FnvHashSet};
+use indicatif::ParallelProgressIterator;
+use rayon::prelude::*;
+
+use crate::{
+    util::{pansn_sample_haplotype, progress_bar},
+    variants::{PathData, PathStep},
+};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+/// For each segment, the (path index, step index) of every step
+/// traversing it.
+pub type NodeOccurrences = FnvHashMap<u64, Vec<(usize, usize)>>;
+
+/// Build the index of where each segment occurs in the paths.
+pub fn node_occurrence_index(paths: &[Vec<PathStep>]) -> NodeOccurrences {
+    debug!("Indexing node occurrences in {} paths", paths.len());
+    let p_bar = progress_bar(paths.len(), false);
+
+    paths
+        .par_iter()
+        .enumerate()
+        .progress_with(p_bar)
+        .fold(NodeOccurrences::default, |mut index, (path_ix, path)| {
+            for (step_ix, &(node, _, _)) in path.iter().enumerate() {
+                index
+                    .entry(node as u64)
+                    .or_default()
+                    .push((path_ix, step_ix));
+            }
+            index
+        })
+        .reduce(NodeOccurrences::default, |mut a, b| {
+            for (node, mut occurrences) in b {
+                a.entry(node).or_default().append(&mut occurrences);
+            }
+            a
+        })
+}
+
+/// How many times a segment is traversed, and by how many distinct
+/// paths and samples.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SegmentDepth {
+    pub steps: usize,
+    pub paths: usize,
+    pub samples: usize,
+}
+
+/// Compute the depth of every segment in the path data. Samples are
+/// taken from PanSN path names, `sample#haplotype#contig`; other
+/// paths are their own sample.
+pub fn segment_depths(
+    path_data: &PathData,
+    index: &NodeOccurrences,
+) -> FnvHashMap<u64, SegmentDepth> {
+    let path_samples: Vec<&[u8]> = path_data
+        .path_names
+        .iter()
+        .map(|name| {
+            pansn_sample_haplotype(name)
+                .map(|(sample, _)| sample)
+                .unwrap_or_else(|| name.as_slice())
+        })
+        .collect();
+
+    path_data
+        .segment_map
+        .par_iter()
+        .map(|(&node, _)| {
+            let node = node as u64;
+            let depth = index
+                .get(&node)
+                .map(|occurrences| {
+                    let paths: FnvHashSet<usize> =
+                        occurrences.iter().map(|&(p, _)| p).collect();
+                    let samples: FnvHashSet<&[u8]> =
+                        paths.iter().map(|&p| path_samples[p]).collect();
+                    SegmentDepth {
+                        steps: occurrences.len(),
+                        paths: paths.len(),
+                        samples: samples.len(),
+                    }
+                })
+                .unwrap_or_default();
+            (node, depth)
+        })
+        .collect()
+}
+
+/// Project a per-segment value onto a path, as bedGraph intervals
+/// `(start, end, value)` with 0-based, end-exclusive coordinates.
+/// Adjacent intervals with the same value are merged.
+pub fn path_bedgraph<F>(
+    path_data: &PathData,
+    path_ix: usize,
+    value: F,
+) -> Vec<(usize, usize, usize)>
+where
+    F: Fn(u64) -> usize,
+{
+    let mut intervals: Vec<(usize, usize, usize)> = Vec::new();
+
+    for &(node, offset, _) in path_data.paths[path_ix].iter() {
+        let len = path_data.segment_map.get(&node).map_or(0, |s| s.len());
+        // Path data offsets are 1-based
+        let start = offset - 1;
+        let end = start + len;
+        let value = value(node as u64);
+
+        match intervals.last_mut() {
+            Some(last) if last.1 == start && last.2 == value => last.1 = end,
+            _ => intervals.push((start, end, value)),
+        }
+    }
+
+    intervals
+}
+
+/// The index of the path with the given name.
+pub fn path_index(path_data: &PathData, name: &str) -> Option<usize> {
+    let name = BString::from(name);
+    path_data.path_names.iter().position(|n| n == &name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variants;
+    use gfa::gfa::Orientation;
+
+    #[test]
+    fn depths_and_bedgraph() {
+        let path = |ids: &[usize]| -> Vec<(usize, Orientation)> {
+            ids.iter().map(|&id| (id, Orientation::Forward)).collect()
+        };
+        let path_data = variants::path_data_from_steps(
+            &[(1, "AAAA"), (2, "C"), (3, "GG")],
+            &[
+                ("a#1#x", &path(&[1, 2, 3])),
+                ("a#2#x", &path(&[1, 3, 3])),
+                ("b#1#x", &path(&[1, 2])),
+            ],
+        );
+
+        let index = node_occurrence_index(&path_data.paths);
+        let depths = segment_depths(&path_data, &index);
+        assert_eq!(
+            depths[&1],
+            SegmentDepth {
+                steps: 3,
+                paths: 3,
+                samples: 2
+            }
+        );
+        assert_eq!(
+            depths[&3],
+            SegmentDepth {
+                steps: 3,
+                paths: 2,
+                samples: 1
+            }
+        );
+
+        let bedgraph = path_bedgraph(&path_data, 0, |n| depths[&n].paths);
+        assert_eq!(bedgraph, vec![(0, 4, 3), (4, 7, 2)]);
+    }
+}
@@ -1,20 +1,152 @@
-use handlegraph::handle::Direction;
-use handlegraph::handlegraph::HandleGraph;
-
-/// Return the inbound and outbound edge counts for each node in the
-/// graph
-pub fn graph_edge_count<T: HandleGraph>(
-    graph: &T,
-) -> Vec<(u64, usize, usize, usize)> {
-    graph
-        .handles_iter()
-        .map(|h| {
-            let inbound = graph.degree(h, Direction::Left);
-            let outbound = graph.degree(h, Direction::Right);
-            let total = inbound + outbound;
-            let id: u64 = h.unpack_number();
-
-            (id, inbound, outbound, total)
-        })
+use bstr::BString;
+use fnv::FnvHashMap;
+use gfa::{gfa::GFA, optfields::OptFields};
+
+/// The links attached to a segment.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SegmentEdges {
+    /// Links attached to the start of the segment
+    pub start: usize,
+    /// Links attached to the end of the segment
+    pub end: usize,
+    /// Links from the segment to itself
+    pub self_loops: usize,
+    /// Links joining the segment to the same side of another segment,
+    /// or to itself, i.e. links that change the orientation
+    pub reversing: usize,
+}
+
+impl SegmentEdges {
+    pub fn total(&self) -> usize {
+        self.start + self.end
+    }
+}
+
+/// Count the links attached to each side of each segment, in the
+/// order of `gfa.segments`. A link leaves a forward segment from its
+/// end, and enters it at its start; the opposite is true for reversed
+/// segments. Self-loops count once for each side they touch.
+pub fn segment_edges<T: OptFields>(gfa: &GFA<BString, T>) -> Vec<SegmentEdges> {
+    let indices: FnvHashMap<&[u8], usize> = gfa
+        .segments
+        .iter()
+        .enumerate()
+        .map(|(ix, s)| (s.name.as_slice(), ix))
+        .collect();
+
+    let mut edges = vec![SegmentEdges::default(); gfa.segments.len()];
+
+    for link in gfa.links.iter() {
+        let from = indices.get(link.from_segment.as_slice()).copied();
+        let to = indices.get(link.to_segment.as_slice()).copied();
+        let reversing = link.from_orient != link.to_orient;
+
+        if let Some(from) = from {
+            if link.from_orient.is_reverse() {
+                edges[from].start += 1;
+            } else {
+                edges[from].end += 1;
+            }
+            if reversing {
+                edges[from].reversing += 1;
+            }
+        }
+
+        if let Some(to) = to {
+            if link.to_orient.is_reverse() {
+                edges[to].end += 1;
+            } else {
+                edges[to].start += 1;
+            }
+            if reversing && from != Some(to) {
+                edges[to].reversing += 1;
+            }
+        }
+
+        if let (Some(from), Some(to)) = (from, to) {
+            if from == to {
+                edges[from].self_loops += 1;
+            }
+        }
+    }
+
+    edges
+}
+
+/// The number of links attached to the start and the end of each
+/// segment, in the order of `gfa.segments`.
+pub fn segment_side_degrees<T: OptFields>(
+    gfa: &GFA<BString, T>,
+) -> Vec<(usize, usize)> {
+    segment_edges(gfa)
+        .iter()
+        .map(|e| (e.start, e.end))
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gfa::gfa::{Link, Orientation, Segment};
+
+    #[test]
+    fn segment_side_edges() {
+        use Orientation::{Backward, Forward};
+
+        let segments = ["a", "b", "c"]
+            .iter()
+            .map(|&name| Segment {
+                name: BString::from(name),
+                sequence: "A".into(),
+                optional: (),
+            })
+            .collect();
+        let link = |from: &str, from_orient, to: &str, to_orient| Link {
+            from_segment: BString::from(from),
+            from_orient,
+            to_segment: BString::from(to),
+            to_orient,
+            overlap: "0M".into(),
+            optional: (),
+        };
+        let gfa: GFA<BString, ()> = GFA {
+            segments,
+            links: vec![
+                link("a", Forward, "b", Forward),
+                // End of b to end of c
+                link("b", Forward, "c", Backward),
+                // End of c to end of c
+                link("c", Forward, "c", Backward),
+            ],
+            ..GFA::default()
+        };
+
+        let edges = segment_edges(&gfa);
+        assert_eq!(
+            edges[0],
+            SegmentEdges {
+                start: 0,
+                end: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            edges[1],
+            SegmentEdges {
+                start: 1,
+                end: 1,
+                self_loops: 0,
+                reversing: 1
+            }
+        );
+        assert_eq!(
+            edges[2],
+            SegmentEdges {
+                start: 0,
+                end: 3,
+                self_loops: 1,
+                reversing: 2
+            }
+        );
+    }
+}
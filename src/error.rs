@@ -0,0 +1,112 @@
+use std::{fmt, io};
+
+/// The errors returned by the commands and library functions. Each
+/// class of error has its own exit code, see `Error::exit_code`.
+#[derive(Debug)]
+pub enum Error {
+    /// An invalid combination or value of command line arguments
+    InvalidArgument(String),
+    /// An error reading or writing a file
+    Io(io::Error),
+    /// An input file that couldn't be parsed, such as a GFA, GAF, BED,
+    /// or bubbles file
+    Parse(String),
+    /// A graph that can't be used for the requested operation, such as
+    /// one missing a reference path, or with paths using segments that
+    /// don't exist
+    InvalidGraph(String),
+    /// Any other error
+    Other(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// The exit code of the process when it fails with this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Other(_) => 1,
+            Error::InvalidArgument(_) => 2,
+            Error::Io(_) => 3,
+            Error::Parse(_) => 4,
+            Error::InvalidGraph(_) => 5,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidArgument(msg) => {
+                write!(f, "Invalid argument: {}", msg)
+            }
+            Error::Io(err) => write!(f, "IO error: {}", err),
+            Error::Parse(msg) => write!(f, "Parse error: {}", msg),
+            Error::InvalidGraph(msg) => write!(f, "Invalid graph: {}", msg),
+            Error::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<gfa::parser::ParseError> for Error {
+    fn from(err: gfa::parser::ParseError) -> Self {
+        Error::Parse(err.to_string())
+    }
+}
+
+impl From<std::num::ParseIntError> for Error {
+    fn from(err: std::num::ParseIntError) -> Self {
+        Error::Parse(err.to_string())
+    }
+}
+
+impl From<std::num::ParseFloatError> for Error {
+    fn from(err: std::num::ParseFloatError) -> Self {
+        Error::Parse(err.to_string())
+    }
+}
+
+impl From<std::str::ParseBoolError> for Error {
+    fn from(err: std::str::ParseBoolError) -> Self {
+        Error::Parse(err.to_string())
+    }
+}
+
+impl From<std::str::Utf8Error> for Error {
+    fn from(err: std::str::Utf8Error) -> Self {
+        Error::Parse(err.to_string())
+    }
+}
+
+impl From<bstr::Utf8Error> for Error {
+    fn from(err: bstr::Utf8Error) -> Self {
+        Error::Parse(err.to_string())
+    }
+}
+
+impl From<regex::Error> for Error {
+    fn from(err: regex::Error) -> Self {
+        Error::InvalidArgument(err.to_string())
+    }
+}
+
+impl From<rayon::ThreadPoolBuildError> for Error {
+    fn from(err: rayon::ThreadPoolBuildError) -> Self {
+        Error::Other(err.to_string())
+    }
+}
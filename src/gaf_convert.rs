@@ -9,12 +9,23 @@ use gfa::{
     optfields::{OptFieldVal, OptFields, OptionalFields},
 };
 
+use crate::{
+    error::{Error, Result},
+    subgraph,
+};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
 type GAF = gfa::gafpaf::GAF<OptionalFields>;
 type PAF = gfa::gafpaf::PAF<OptionalFields>;
 
-fn set_cigar(opts: &mut OptionalFields, cg: CIGAR) {
-    let cg_tag = opts.iter_mut().find(|o| &o.tag == b"cg").unwrap();
+/// Replace the value of the `cg` tag, or return None if there's no
+/// such tag.
+fn set_cigar(opts: &mut OptionalFields, cg: CIGAR) -> Option<()> {
+    let cg_tag = opts.iter_mut().find(|o| &o.tag == b"cg")?;
     cg_tag.value = OptFieldVal::Z(cg.to_string().into());
+    Some(())
 }
 
 fn get_cigar<T: OptFields>(opts: &T) -> Option<CIGAR> {
@@ -46,16 +57,22 @@ fn gaf_to_paf_clone(gaf: &GAF) -> PAF {
     }
 }
 
+fn find_segment_index<T: OptFields>(
+    segs: &[Segment<BString, T>],
+    name: &[u8],
+) -> Option<usize> {
+    segs.binary_search_by(|s| {
+        let seg: &[u8] = s.name.as_ref();
+        seg.cmp(name)
+    })
+    .ok()
+}
+
 fn find_segment<'a, T: OptFields>(
     segs: &'a [Segment<BString, T>],
     name: &[u8],
 ) -> Option<&'a Segment<BString, T>> {
-    let ix = segs
-        .binary_search_by(|s| {
-            let seg: &[u8] = s.name.as_ref();
-            seg.cmp(name)
-        })
-        .ok()?;
+    let ix = find_segment_index(segs, name)?;
     segs.get(ix)
 }
 
@@ -81,7 +98,7 @@ fn cmp_links<T: OptFields>(
     cmp_links_find(l1, &l2.from_segment, &l2.to_segment)
 }
 
-fn unwrap_step(step: &GAFStep) -> (Orientation, &[u8]) {
+pub(crate) fn unwrap_step(step: &GAFStep) -> (Orientation, &[u8]) {
     match step {
         GAFStep::SegId(o, id) => (*o, id.as_ref()),
         GAFStep::StableIntv(o, id, _from, _to) => (*o, id.as_ref()),
@@ -92,24 +109,31 @@ fn unwrap_step(step: &GAFStep) -> (Orientation, &[u8]) {
 fn gaf_line_to_pafs<T: OptFields>(
     segments: &[Segment<BString, T>],
     gaf: &GAF,
-) -> Vec<PAF> {
+) -> Result<Vec<PAF>> {
     match &gaf.path {
         GAFPath::StableId(id) => {
             let paf = PAF {
                 target_seq_name: id.clone(),
                 ..gaf_to_paf_clone(gaf)
             };
-            vec![paf]
+            Ok(vec![paf])
         }
         GAFPath::OrientIntv(steps) => {
-            let seg_steps: Vec<(Orientation, &Segment<_, _>)> = steps
+            let seg_steps = steps
                 .iter()
                 .map(|s| {
                     let (o, id) = unwrap_step(s);
-                    let segment = find_segment(segments, id).unwrap();
-                    (o, segment)
+                    let segment =
+                        find_segment(segments, id).ok_or_else(|| {
+                            Error::InvalidGraph(format!(
+                                "GAF record {} uses missing segment {}",
+                                gaf.seq_name,
+                                id.as_bstr()
+                            ))
+                        })?;
+                    Ok((o, segment))
                 })
-                .collect();
+                .collect::<Result<Vec<(Orientation, &Segment<_, _>)>>>()?;
 
             let mut query_index = gaf.seq_range.0;
             let mut tgt_offset = gaf.path_range.0;
@@ -119,8 +143,12 @@ fn gaf_line_to_pafs<T: OptFields>(
 
             let mut pafs = Vec::new();
 
-            let mut gaf_cigar =
-                get_gaf_cigar(gaf).expect("missing cigar in GAF record");
+            let mut gaf_cigar = get_gaf_cigar(gaf).ok_or_else(|| {
+                Error::Parse(format!(
+                    "GAF record {} is missing its cigar",
+                    gaf.seq_name
+                ))
+            })?;
 
             for (orient, target) in seg_steps {
                 let seg_len = target.sequence.len();
@@ -186,7 +214,12 @@ fn gaf_line_to_pafs<T: OptFields>(
                     }
                 });
 
-                set_cigar(&mut optional, paf_cigar);
+                set_cigar(&mut optional, paf_cigar).ok_or_else(|| {
+                    Error::Parse(format!(
+                        "GAF record {} is missing its cigar",
+                        gaf.seq_name
+                    ))
+                })?;
 
                 let block_length = step_len;
 
@@ -211,39 +244,247 @@ fn gaf_line_to_pafs<T: OptFields>(
                 tgt_offset = 0;
             }
 
-            pafs
+            Ok(pafs)
         }
     }
 }
 
+pub(crate) fn load_gaf(gaf_path: &Path) -> Result<Vec<GAF>> {
+    let file = File::open(gaf_path)?;
+    let lines = BufReader::new(file).byte_lines();
+    let mut gafs: Vec<GAF> = Vec::new();
+
+    for (i, line) in lines.enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let fields = line.split_str(b"\t");
+        let gaf = parse_gaf(fields).ok_or_else(|| {
+            Error::Parse(format!(
+                "Invalid GAF record on line {} of {}",
+                i + 1,
+                gaf_path.display()
+            ))
+        })?;
+        gafs.push(gaf);
+    }
+
+    Ok(gafs)
+}
+
 pub fn gaf_to_paf<T: OptFields>(
     gfa: GFA<BString, T>,
     gaf_path: &Path,
-) -> Vec<PAF> {
+) -> Result<Vec<PAF>> {
     let mut segments = gfa.segments;
     segments.sort_by(|s1, s2| s1.name.cmp(&s2.name));
     let mut links = gfa.links;
     links.sort_by(cmp_links);
 
-    let file = File::open(gaf_path).unwrap();
-    let lines = BufReader::new(file).byte_lines().map(|l| l.unwrap());
-    let mut gafs: Vec<GAF> = Vec::new();
+    let gafs = load_gaf(gaf_path)?;
 
-    for (i, line) in lines.enumerate() {
-        let fields = line.split_str(b"\t");
-        if let Some(gaf) = parse_gaf(fields) {
-            gafs.push(gaf);
+    let mut pafs: Vec<PAF> = Vec::new();
+
+    for gaf in gafs.iter() {
+        let cur_pafs = gaf_line_to_pafs(&segments, gaf)?;
+        pafs.extend(cur_pafs);
+    }
+
+    Ok(pafs)
+}
+
+/// Read coverage of a segment.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SegmentCoverage {
+    /// Number of alignments overlapping the segment
+    pub alignments: usize,
+    /// Number of segment bases covered by those alignments
+    pub bases: usize,
+}
+
+impl SegmentCoverage {
+    /// The mean depth across the segment.
+    pub fn depth(&self, length: usize) -> f64 {
+        if length == 0 {
+            0.0
         } else {
-            eprintln!("Error parsing GAF line {}", i);
+            self.bases as f64 / length as f64
         }
     }
+}
 
-    let mut pafs: Vec<PAF> = Vec::new();
+/// The segments an alignment overlaps, as pairs of segment index and
+/// number of bases covered, in path order. An alignment visiting the
+/// same segment several times produces several entries. Returns None
+/// if the alignment is to a stable path, or uses an unknown segment.
+// must take sorted segments
+fn gaf_segment_bases<T: OptFields>(
+    segments: &[Segment<BString, T>],
+    gaf: &GAF,
+) -> Option<Vec<(usize, usize)>> {
+    let steps = match &gaf.path {
+        GAFPath::StableId(_) => return None,
+        GAFPath::OrientIntv(steps) => steps,
+    };
+
+    let (path_start, path_end) = gaf.path_range;
+    let mut offset = 0;
+    let mut covered = Vec::with_capacity(steps.len());
+
+    for step in steps.iter() {
+        let (_, id) = unwrap_step(step);
+        let ix = find_segment_index(segments, id)?;
+        let len = subgraph::segment_length(&segments[ix]);
+
+        let start = offset.max(path_start);
+        let end = (offset + len).min(path_end);
+        if start < end {
+            covered.push((ix, end - start));
+        }
+        offset += len;
+    }
 
-    gafs.iter().for_each(|gaf| {
-        let cur_pafs = gaf_line_to_pafs(&segments, &gaf);
-        pafs.extend(cur_pafs);
-    });
+    Some(covered)
+}
+
+/// Compute the read coverage of each segment from GAF records,
+/// skipping alignments with a mapping quality below `min_mapq`. The
+/// segments must be sorted by name, and the coverage is returned in
+/// the same order.
+///
+/// Alignments to stable paths, or to segments missing from the
+/// graph, are skipped with a warning.
+pub fn gaf_segment_coverage<T: OptFields>(
+    segments: &[Segment<BString, T>],
+    gafs: &[GAF],
+    min_mapq: u8,
+) -> Vec<SegmentCoverage> {
+    let mut coverage = vec![SegmentCoverage::default(); segments.len()];
+
+    let mut filtered = 0;
+    let mut skipped = 0;
+
+    for gaf in gafs.iter() {
+        if gaf.quality < min_mapq {
+            filtered += 1;
+            continue;
+        }
+
+        let covered = match gaf_segment_bases(segments, gaf) {
+            Some(covered) => covered,
+            None => {
+                skipped += 1;
+                continue;
+            }
+        };
+
+        for &(ix, bases) in covered.iter() {
+            coverage[ix].bases += bases;
+        }
+
+        let mut visited: Vec<usize> =
+            covered.iter().map(|&(ix, _)| ix).collect();
+        visited.sort_unstable();
+        visited.dedup();
+        for ix in visited {
+            coverage[ix].alignments += 1;
+        }
+    }
 
-    pafs
+    debug!(
+        "Filtered {} alignments with mapping quality below {}",
+        filtered, min_mapq
+    );
+    if skipped > 0 {
+        warn!(
+            "Skipped {} alignments to stable paths or unknown segments",
+            skipped
+        );
+    }
+
+    coverage
+}
+
+/// Load the GAF records in `gaf_path` and compute the coverage of
+/// each segment in the graph, as in `gaf_segment_coverage`. The
+/// segments of the graph are sorted by name.
+pub fn gaf_coverage<T: OptFields>(
+    gfa: &mut GFA<BString, T>,
+    gaf_path: &Path,
+    min_mapq: u8,
+) -> Result<Vec<SegmentCoverage>> {
+    gfa.segments.sort_by(|s1, s2| s1.name.cmp(&s2.name));
+    let gafs = load_gaf(gaf_path)?;
+    info!("Loaded {} GAF records", gafs.len());
+    Ok(gaf_segment_coverage(&gfa.segments, &gafs, min_mapq))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(name: &str, seq: &str) -> Segment<BString, OptionalFields> {
+        Segment {
+            name: name.into(),
+            sequence: seq.into(),
+            optional: Vec::new(),
+        }
+    }
+
+    fn gaf(steps: &[&str], path_range: (usize, usize), quality: u8) -> GAF {
+        let steps = steps
+            .iter()
+            .map(|&s| GAFStep::SegId(Orientation::Forward, s.into()))
+            .collect();
+        GAF {
+            seq_name: "read".into(),
+            seq_len: path_range.1 - path_range.0,
+            seq_range: (0, path_range.1 - path_range.0),
+            strand: Orientation::Forward,
+            path: GAFPath::OrientIntv(steps),
+            path_len: 0,
+            path_range,
+            residue_matches: 0,
+            block_length: 0,
+            quality,
+            optional: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn coverage_of_segments() {
+        let segments = vec![
+            segment("a", "AAAA"),
+            segment("b", "CC"),
+            segment("c", "GGGGGG"),
+        ];
+
+        let gafs = vec![
+            // 2 bases of a, all of b, 3 bases of c
+            gaf(&["a", "b", "c"], (2, 9), 60),
+            // c, then back through b into c again
+            gaf(&["c", "b", "c"], (4, 10), 30),
+            gaf(&["a"], (0, 4), 0),
+        ];
+
+        let coverage = gaf_segment_coverage(&segments, &gafs, 10);
+
+        let expected = vec![
+            SegmentCoverage {
+                alignments: 1,
+                bases: 2,
+            },
+            SegmentCoverage {
+                alignments: 2,
+                bases: 4,
+            },
+            SegmentCoverage {
+                alignments: 2,
+                bases: 7,
+            },
+        ];
+        assert_eq!(coverage, expected);
+        assert_eq!(coverage[2].depth(6), 7.0 / 6.0);
+    }
 }
@@ -0,0 +1,413 @@
+use bstr::BString;
+use fnv::{FnvHashMap, FnvHashSet};
+use rayon::prelude::*;
+
+use gfa::{
+    gafpaf::{GAFPath, GAF},
+    optfields::OptFields,
+};
+
+use crate::{
+    gaf_convert::unwrap_step,
+    util::reverse_complement,
+    variants::{path_data_sub_paths, vcf::VCFRecord, PathData, PathIndices},
+};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+/// A walk through a bubble, including both of its boundary nodes, as
+/// pairs of node and whether the node is traversed in reverse.
+pub type Traversal = Vec<(usize, bool)>;
+
+/// A bubble traversed by the reference path, and its alleles.
+#[derive(Debug, Clone)]
+pub struct Site {
+    /// The boundary node the reference enters the bubble through
+    pub from: u64,
+    /// The boundary node the reference leaves the bubble through
+    pub to: u64,
+    /// The 1-based position on the reference of the last base of
+    /// `from`
+    pub position: usize,
+    /// The anchor base, the last base of `from`
+    pub anchor: u8,
+    /// The allele sequences between the boundary nodes, with the
+    /// reference allele first. Traversals spelling the same sequence
+    /// share an allele.
+    pub sequences: Vec<BString>,
+    /// The allele index of each traversal, oriented along the
+    /// reference
+    pub traversals: FnvHashMap<Traversal, usize>,
+}
+
+/// The same traversal, walked in the other direction.
+fn reverse_traversal(traversal: &[(usize, bool)]) -> Traversal {
+    traversal.iter().rev().map(|&(n, rev)| (n, !rev)).collect()
+}
+
+fn oriented_sequence(seq: &[u8], reverse: bool) -> Vec<u8> {
+    if reverse {
+        reverse_complement(seq)
+    } else {
+        seq.to_vec()
+    }
+}
+
+impl Site {
+    /// The allele index of a traversal through the bubble, in either
+    /// direction.
+    pub fn allele(&self, traversal: &[(usize, bool)]) -> Option<usize> {
+        if traversal.first()?.0 == self.from as usize {
+            self.traversals.get(traversal).copied()
+        } else {
+            self.traversals.get(&reverse_traversal(traversal)).copied()
+        }
+    }
+
+    fn max_traversal_len(&self) -> usize {
+        self.traversals.keys().map(|t| t.len()).max().unwrap_or(0)
+    }
+}
+
+/// Find the alleles of each bubble from the paths traversing it, with
+/// the reference path's allele as the reference allele, and the
+/// bubble's ends ordered along the reference. Bubbles that the
+/// reference path doesn't traverse, or with only one allele, are
+/// skipped.
+pub fn bubble_sites(
+    path_data: &PathData,
+    path_indices: &PathIndices,
+    ref_ix: usize,
+    bubbles: &[(u64, u64)],
+) -> Vec<Site> {
+    let sequence = |&(node, reverse): &(usize, bool)| {
+        oriented_sequence(&path_data.segment_map[&node], reverse)
+    };
+
+    let mut sites: Vec<Site> = bubbles
+        .par_iter()
+        .filter_map(|&(from, to)| {
+            let sub_paths =
+                path_data_sub_paths(path_data, path_indices, from, to)?;
+
+            let (_, ref_steps) =
+                sub_paths.iter().find(|(p, _)| *p == ref_ix)?;
+            let ref_traversal: Traversal = ref_steps
+                .iter()
+                .map(|&(n, _, o)| (n, o.is_reverse()))
+                .collect();
+            let start = ref_traversal[0].0;
+            let end = ref_traversal[ref_traversal.len() - 1].0;
+
+            let (_, ref_offset, _) = ref_steps[0];
+            let entry_seq = sequence(&ref_traversal[0]);
+            let position = ref_offset + entry_seq.len() - 1;
+            let anchor = *entry_seq.last()?;
+
+            let mut site = Site {
+                from: start as u64,
+                to: end as u64,
+                position,
+                anchor,
+                sequences: Vec::new(),
+                traversals: FnvHashMap::default(),
+            };
+
+            let others = sub_paths.iter().filter(|(p, _)| *p != ref_ix).map(
+                |(_, steps)| {
+                    let traversal: Traversal = steps
+                        .iter()
+                        .map(|&(n, _, o)| (n, o.is_reverse()))
+                        .collect();
+                    if traversal[0].0 == start {
+                        traversal
+                    } else {
+                        reverse_traversal(&traversal)
+                    }
+                },
+            );
+
+            for traversal in std::iter::once(ref_traversal).chain(others) {
+                if site.traversals.contains_key(&traversal) {
+                    continue;
+                }
+                let inner = &traversal[1..traversal.len() - 1];
+                let seq: BString = inner.iter().flat_map(sequence).collect();
+
+                let allele = match site.sequences.iter().position(|s| s == &seq)
+                {
+                    Some(ix) => ix,
+                    None => {
+                        site.sequences.push(seq);
+                        site.sequences.len() - 1
+                    }
+                };
+                site.traversals.insert(traversal, allele);
+            }
+
+            if site.sequences.len() < 2 {
+                return None;
+            }
+
+            Some(site)
+        })
+        .collect();
+
+    sites.sort_by_key(|s| s.position);
+    sites
+}
+
+/// The segments an alignment visits, in order. Returns None for
+/// alignments to stable paths, or to segments without an integer
+/// name.
+pub fn gaf_node_steps<T: OptFields>(gaf: &GAF<T>) -> Option<Traversal> {
+    match &gaf.path {
+        GAFPath::StableId(_) => None,
+        GAFPath::OrientIntv(steps) => steps
+            .iter()
+            .map(|step| {
+                let (orient, id) = unwrap_step(step);
+                let node = std::str::from_utf8(id).ok()?.parse().ok()?;
+                Some((node, orient.is_reverse()))
+            })
+            .collect(),
+    }
+}
+
+/// Count the reads supporting each allele of each site. A read
+/// supports an allele if it traverses the whole bubble, from one
+/// boundary node to the other, along one of the allele's traversals;
+/// each read is counted at most once per allele.
+pub fn allele_support(sites: &[Site], reads: &[Traversal]) -> Vec<Vec<usize>> {
+    let mut boundaries: FnvHashMap<usize, Vec<usize>> = FnvHashMap::default();
+    for (ix, site) in sites.iter().enumerate() {
+        boundaries.entry(site.from as usize).or_default().push(ix);
+        if site.to != site.from {
+            boundaries.entry(site.to as usize).or_default().push(ix);
+        }
+    }
+
+    let max_lens: Vec<usize> =
+        sites.iter().map(Site::max_traversal_len).collect();
+
+    let empty = || -> Vec<Vec<usize>> {
+        sites.iter().map(|s| vec![0; s.sequences.len()]).collect()
+    };
+
+    reads
+        .par_iter()
+        .fold(empty, |mut support, read| {
+            let mut seen: FnvHashSet<(usize, usize)> = FnvHashSet::default();
+
+            for (i, &(node, _)) in read.iter().enumerate() {
+                let site_ixs = match boundaries.get(&node) {
+                    Some(ixs) => ixs,
+                    None => continue,
+                };
+                for &site_ix in site_ixs.iter() {
+                    let site = &sites[site_ix];
+                    let other = if node == site.from as usize {
+                        site.to as usize
+                    } else {
+                        site.from as usize
+                    };
+                    let end = read.len().min(i + max_lens[site_ix]);
+                    let exit = (i + 1..end).find(|&j| read[j].0 == other);
+                    if let Some(j) = exit {
+                        if let Some(allele) = site.allele(&read[i..=j]) {
+                            seen.insert((site_ix, allele));
+                        }
+                    }
+                }
+            }
+
+            for (site_ix, allele) in seen {
+                support[site_ix][allele] += 1;
+            }
+            support
+        })
+        .reduce(empty, |mut a, b| {
+            for (x, y) in a.iter_mut().zip(b) {
+                for (x, y) in x.iter_mut().zip(y) {
+                    *x += y;
+                }
+            }
+            a
+        })
+}
+
+/// A diploid genotype call.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Genotype {
+    pub alleles: (usize, usize),
+    /// Phred-scaled probability that the genotype is wrong, capped
+    /// at 99
+    pub quality: u32,
+    /// Phred-scaled probability that the genotype is homozygous
+    /// reference
+    pub ref_quality: f64,
+}
+
+/// The log10 likelihood of each unordered diploid genotype given the
+/// read support for each allele, with a read supporting the wrong
+/// allele with probability `error_rate`.
+pub fn genotype_likelihoods(
+    support: &[usize],
+    error_rate: f64,
+) -> Vec<((usize, usize), f64)> {
+    let n = support.len();
+    let p_read = |k: usize, allele: usize| {
+        if k == allele {
+            1.0 - error_rate
+        } else {
+            error_rate / (n - 1).max(1) as f64
+        }
+    };
+
+    let mut likelihoods = Vec::with_capacity(n * (n + 1) / 2);
+    for b in 0..n {
+        for a in 0..=b {
+            let log_l = support
+                .iter()
+                .enumerate()
+                .filter(|&(_, &count)| count > 0)
+                .map(|(k, &count)| {
+                    let p = 0.5 * p_read(k, a) + 0.5 * p_read(k, b);
+                    count as f64 * p.log10()
+                })
+                .sum();
+            likelihoods.push(((a, b), log_l));
+        }
+    }
+    likelihoods
+}
+
+/// Call the most likely diploid genotype, assuming a flat prior over
+/// genotypes. Returns None if no reads support any allele.
+pub fn call_genotype(support: &[usize], error_rate: f64) -> Option<Genotype> {
+    if support.iter().sum::<usize>() == 0 {
+        return None;
+    }
+
+    let likelihoods = genotype_likelihoods(support, error_rate);
+    let &(alleles, best) = likelihoods
+        .iter()
+        .max_by(|(_, x), (_, y)| x.partial_cmp(y).unwrap())?;
+
+    // Posteriors relative to the best genotype, to avoid underflow
+    let total: f64 =
+        likelihoods.iter().map(|(_, l)| 10f64.powf(l - best)).sum();
+    let error = (total - 1.0) / total;
+    let quality = if error <= 0.0 {
+        99
+    } else {
+        ((-10.0 * error.log10()).round() as u32).min(99)
+    };
+
+    let hom_ref = 10f64.powf(likelihoods[0].1 - best) / total;
+    let ref_quality = if hom_ref <= 0.0 {
+        999.0
+    } else {
+        (-10.0 * hom_ref.log10()).min(999.0)
+    };
+
+    Some(Genotype {
+        alleles,
+        quality,
+        ref_quality,
+    })
+}
+
+/// A single-sample VCF record for the site, with the GT, AD, DP, and
+/// GQ fields.
+pub fn site_vcf_record(
+    ref_name: &[u8],
+    site: &Site,
+    support: &[usize],
+    genotype: Option<&Genotype>,
+) -> VCFRecord {
+    let allele_seq = |seq: &BString| -> BString {
+        std::iter::once(site.anchor)
+            .chain(seq.iter().copied())
+            .collect()
+    };
+
+    let reference = allele_seq(&site.sequences[0]);
+    let alts: Vec<BString> =
+        site.sequences[1..].iter().map(allele_seq).collect();
+
+    let depth: usize = support.iter().sum();
+    let ad: Vec<String> = support.iter().map(|c| c.to_string()).collect();
+
+    let sample = match genotype {
+        Some(gt) => format!(
+            "{}/{}:{}:{}:{}",
+            gt.alleles.0,
+            gt.alleles.1,
+            ad.join(","),
+            depth,
+            gt.quality
+        ),
+        None => format!("./.:{}:{}:.", ad.join(","), depth),
+    };
+
+    VCFRecord {
+        chromosome: ref_name.into(),
+        position: site.position as i64,
+        id: Some(format!(">{}>{}", site.from, site.to).into()),
+        reference,
+        alternate: Some(bstr::join(",", alts).into()),
+        quality: genotype.map(|gt| gt.ref_quality.round() as i32),
+        filter: None,
+        info: None,
+        format: Some("GT:AD:DP:GQ".into()),
+        sample_name: Some(sample.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn genotype_from_support() {
+        let het = call_genotype(&[10, 9], 0.01).unwrap();
+        assert_eq!(het.alleles, (0, 1));
+        assert_eq!(het.quality, 99);
+
+        let hom_alt = call_genotype(&[0, 2, 15], 0.01).unwrap();
+        assert_eq!(hom_alt.alleles, (2, 2));
+
+        let weak = call_genotype(&[1, 0], 0.01).unwrap();
+        assert_eq!(weak.alleles, (0, 0));
+        assert!(weak.quality < 10);
+
+        assert!(call_genotype(&[0, 0], 0.01).is_none());
+    }
+
+    #[test]
+    fn support_in_both_directions() {
+        let mut traversals = FnvHashMap::default();
+        traversals.insert(vec![(1, false), (2, false), (4, false)], 0);
+        traversals.insert(vec![(1, false), (3, false), (4, false)], 1);
+        let site = Site {
+            from: 1,
+            to: 4,
+            position: 5,
+            anchor: b'A',
+            sequences: vec!["C".into(), "G".into()],
+            traversals,
+        };
+
+        let reads = vec![
+            vec![(0, false), (1, false), (2, false), (4, false)],
+            vec![(4, true), (3, true), (1, true)],
+            vec![(1, false), (3, false)],
+            vec![(5, false), (4, true), (3, true), (1, true)],
+        ];
+
+        let support = allele_support(&[site], &reads);
+        assert_eq!(support, vec![vec![1, 2]]);
+    }
+}
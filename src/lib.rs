@@ -1,6 +1,16 @@
+pub mod bubbles;
+pub mod chop;
 pub mod commands;
+pub mod depth;
 pub mod edges;
+pub mod error;
 pub mod gaf_convert;
+pub mod genotype;
+pub mod pangenome;
+pub mod sequence;
+pub mod sort;
+pub mod stats;
 pub mod subgraph;
 pub mod util;
+pub mod validate;
 pub mod variants;
@@ -5,14 +5,20 @@ use std::path::PathBuf;
 use gfautil::{
     commands,
     commands::{
-        convert_names::GfaIdConvertArgs, gaf2paf::GAF2PAFArgs,
-        gfa2vcf::GFA2VCFArgs, snps::SNPArgs, subgraph::SubgraphArgs, Result,
+        add_sequences::AddSequencesArgs, call::CallArgs, chop::ChopArgs,
+        components::ComponentsArgs, convert_names::GfaIdConvertArgs,
+        coverage::CoverageArgs, depth::DepthArgs, gaf2paf::GAF2PAFArgs,
+        gfa2vcf::GFA2VCFArgs, pangenome::PangenomeArgs,
+        path2fasta::Path2FastaArgs, saboten::UltrabubblesArgs,
+        seg2fasta::Seg2FastaArgs, snps::SNPArgs, sort::SortArgs,
+        stats::StatsArgs, subgraph::SubgraphArgs, Result,
     },
 };
 
 #[derive(StructOpt, Debug)]
 enum Command {
     Subgraph(SubgraphArgs),
+    /// Print the number of links on each side of each segment
     EdgeCount,
     #[structopt(name = "gaf2paf")]
     Gaf2Paf(GAF2PAFArgs),
@@ -23,7 +29,32 @@ enum Command {
     #[structopt(name = "snps")]
     Snps(SNPArgs),
     #[structopt(name = "ultrabubbles")]
-    Saboten,
+    Saboten(UltrabubblesArgs),
+    #[structopt(name = "components")]
+    Components(ComponentsArgs),
+    #[structopt(name = "stats")]
+    Stats(StatsArgs),
+    #[structopt(name = "pangenome")]
+    Pangenome(PangenomeArgs),
+    #[structopt(name = "depth")]
+    Depth(DepthArgs),
+    #[structopt(name = "coverage")]
+    Coverage(CoverageArgs),
+    #[structopt(name = "call")]
+    Call(CallArgs),
+    #[structopt(name = "path2fasta")]
+    Path2Fasta(Path2FastaArgs),
+    #[structopt(name = "seg2fasta")]
+    Seg2Fasta(Seg2FastaArgs),
+    #[structopt(name = "add-sequences")]
+    AddSequences(AddSequencesArgs),
+    /// Check the GFA for problems, such as missing segments or links,
+    /// and report them by line number
+    Validate,
+    #[structopt(name = "sort")]
+    Sort(SortArgs),
+    #[structopt(name = "chop")]
+    Chop(ChopArgs),
 }
 
 #[derive(StructOpt, Debug)]
@@ -70,11 +101,7 @@ fn init_logger(opt: &LogOpt) {
     builder.init();
 }
 
-fn main() -> Result<()> {
-    let opt = Opt::from_args();
-
-    init_logger(&opt.log_opts);
-
+fn run(opt: Opt) -> Result<()> {
     if let Some(threads) = &opt.threads {
         log::info!("Initializing threadpool to use {} threads", threads);
         rayon::ThreadPoolBuilder::new()
@@ -101,9 +128,56 @@ fn main() -> Result<()> {
         Command::GfaSegmentIdConversion(args) => {
             commands::convert_names::convert_segment_ids(&opt.in_gfa, &args)?;
         }
-        Command::Saboten => {
-            commands::saboten::run_saboten(&opt.in_gfa)?;
+        Command::Saboten(args) => {
+            commands::saboten::run_saboten(&opt.in_gfa, &args)?;
+        }
+        Command::Components(args) => {
+            commands::components::components(&opt.in_gfa, &args)?;
+        }
+        Command::Stats(args) => {
+            commands::stats::graph_stats(&opt.in_gfa, &args)?;
+        }
+        Command::Pangenome(args) => {
+            commands::pangenome::pangenome(&opt.in_gfa, &args)?;
+        }
+        Command::Depth(args) => {
+            commands::depth::depth(&opt.in_gfa, &args)?;
+        }
+        Command::Coverage(args) => {
+            commands::coverage::coverage(&opt.in_gfa, &args)?;
+        }
+        Command::Call(args) => {
+            commands::call::call(&opt.in_gfa, &args)?;
+        }
+        Command::Path2Fasta(args) => {
+            commands::path2fasta::path2fasta(&opt.in_gfa, &args)?;
+        }
+        Command::Seg2Fasta(args) => {
+            commands::seg2fasta::seg2fasta(&opt.in_gfa, &args)?;
+        }
+        Command::AddSequences(args) => {
+            commands::add_sequences::add_sequences(&opt.in_gfa, &args)?;
+        }
+        Command::Validate => {
+            commands::validate::validate(&opt.in_gfa)?;
+        }
+        Command::Sort(args) => {
+            commands::sort::sort(&opt.in_gfa, &args)?;
+        }
+        Command::Chop(args) => {
+            commands::chop::chop(&opt.in_gfa, &args)?;
         }
     }
     Ok(())
 }
+
+fn main() {
+    let opt = Opt::from_args();
+
+    init_logger(&opt.log_opts);
+
+    if let Err(err) = run(opt) {
+        eprintln!("Error: {}", err);
+        std::process::exit(err.exit_code());
+    }
+}
@@ -0,0 +1,279 @@
+use bstr::BString;
+use fnv::FnvHashMap;
+use rayon::prelude::*;
+
+use crate::{
+    util::{pansn_sample_haplotype, XorShiftRng},
+    variants::PathData,
+};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+/// The segment composition of a set of path groups, either single
+/// paths or PanSN samples.
+#[derive(Debug, Clone)]
+pub struct Pangenome {
+    pub group_names: Vec<BString>,
+    /// The distinct segments of each group, as indices into
+    /// `segment_lengths`
+    group_segments: Vec<Vec<usize>>,
+    /// The segment IDs, sorted
+    pub segment_ids: Vec<usize>,
+    pub segment_lengths: Vec<usize>,
+    /// The number of groups traversing each segment
+    pub segment_counts: Vec<usize>,
+}
+
+/// Segment counts and total lengths of each part of the pangenome.
+/// Core segments are traversed by at least the core threshold of the
+/// groups, cloud segments by only one group, and shell segments by
+/// the rest. Segments not traversed by any path are uncovered.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Partition {
+    pub core: (usize, usize),
+    pub shell: (usize, usize),
+    pub cloud: (usize, usize),
+    pub uncovered: (usize, usize),
+}
+
+/// The part of the pangenome a segment is in, as in `Partition`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Part {
+    Core,
+    Shell,
+    Cloud,
+    Uncovered,
+}
+
+impl std::fmt::Display for Part {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let part = match self {
+            Part::Core => "core",
+            Part::Shell => "shell",
+            Part::Cloud => "cloud",
+            Part::Uncovered => "uncovered",
+        };
+        write!(f, "{}", part)
+    }
+}
+
+impl Pangenome {
+    /// Group the paths by PanSN sample if `by_sample` is true, or
+    /// treat each path as its own group. Paths that aren't named
+    /// using PanSN are their own sample.
+    pub fn from_path_data(path_data: &PathData, by_sample: bool) -> Self {
+        let mut segment_ids: Vec<usize> =
+            path_data.segment_map.keys().copied().collect();
+        segment_ids.sort_unstable();
+
+        let segment_indices: FnvHashMap<usize, usize> = segment_ids
+            .iter()
+            .enumerate()
+            .map(|(ix, &id)| (id, ix))
+            .collect();
+
+        let segment_lengths = segment_ids
+            .iter()
+            .map(|id| path_data.segment_map[id].len())
+            .collect();
+
+        let mut group_names: Vec<BString> = Vec::new();
+        let mut group_paths: Vec<Vec<usize>> = Vec::new();
+        let mut group_ixs: FnvHashMap<BString, usize> = FnvHashMap::default();
+
+        for (path_ix, name) in path_data.path_names.iter().enumerate() {
+            let group: BString = if by_sample {
+                pansn_sample_haplotype(name)
+                    .map(|(sample, _)| sample.into())
+                    .unwrap_or_else(|| name.clone())
+            } else {
+                name.clone()
+            };
+            let group_ix =
+                *group_ixs.entry(group.clone()).or_insert_with(|| {
+                    group_names.push(group);
+                    group_paths.push(Vec::new());
+                    group_names.len() - 1
+                });
+            group_paths[group_ix].push(path_ix);
+        }
+
+        let group_segments: Vec<Vec<usize>> = group_paths
+            .par_iter()
+            .map(|path_ixs| {
+                let mut segments: Vec<usize> = path_ixs
+                    .iter()
+                    .flat_map(|&ix| path_data.paths[ix].iter())
+                    .filter_map(|(id, _, _)| segment_indices.get(id).copied())
+                    .collect();
+                segments.sort_unstable();
+                segments.dedup();
+                segments
+            })
+            .collect();
+
+        let mut segment_counts = vec![0; segment_ids.len()];
+        for segments in group_segments.iter() {
+            for &seg in segments.iter() {
+                segment_counts[seg] += 1;
+            }
+        }
+
+        Self {
+            group_names,
+            group_segments,
+            segment_ids,
+            segment_lengths,
+            segment_counts,
+        }
+    }
+
+    /// The number of segments, and their total length, traversed by
+    /// exactly `k` groups, for each `k` from 0 to the number of
+    /// groups.
+    pub fn histogram(&self) -> Vec<(usize, usize)> {
+        let mut hist = vec![(0, 0); self.group_names.len() + 1];
+        for (&count, &len) in
+            self.segment_counts.iter().zip(self.segment_lengths.iter())
+        {
+            hist[count].0 += 1;
+            hist[count].1 += len;
+        }
+        hist
+    }
+
+    /// The part of the pangenome a segment traversed by `count`
+    /// groups is in. A segment is core if it's traversed by at least
+    /// `core_fraction` of the groups.
+    pub fn part(&self, count: usize, core_fraction: f64) -> Part {
+        let groups = self.group_names.len();
+        let core_min = ((core_fraction * groups as f64).ceil() as usize).max(1);
+
+        if count == 0 {
+            Part::Uncovered
+        } else if count >= core_min {
+            Part::Core
+        } else if count == 1 {
+            Part::Cloud
+        } else {
+            Part::Shell
+        }
+    }
+
+    /// Split the segments into core, shell, and cloud, as in `part`.
+    pub fn partition(&self, core_fraction: f64) -> Partition {
+        let mut partition = Partition::default();
+        for (count, (segments, len)) in self.histogram().into_iter().enumerate()
+        {
+            let part = match self.part(count, core_fraction) {
+                Part::Core => &mut partition.core,
+                Part::Shell => &mut partition.shell,
+                Part::Cloud => &mut partition.cloud,
+                Part::Uncovered => &mut partition.uncovered,
+            };
+            part.0 += segments;
+            part.1 += len;
+        }
+        partition
+    }
+
+    /// The pangenome growth curve: the total length of the segments
+    /// traversed by the first `k` groups, for `k` from 1 to the number
+    /// of groups, over random permutations of the groups. Returns the
+    /// mean, minimum, and maximum over the permutations for each `k`.
+    pub fn growth(
+        &self,
+        permutations: usize,
+        seed: u64,
+    ) -> Vec<(f64, usize, usize)> {
+        let groups = self.group_names.len();
+        if groups == 0 || permutations == 0 {
+            return Vec::new();
+        }
+
+        let curves: Vec<Vec<usize>> = (0..permutations)
+            .into_par_iter()
+            .map(|perm| {
+                let mut rng = XorShiftRng::new(seed.wrapping_add(perm as u64));
+                let mut order: Vec<usize> = (0..groups).collect();
+                rng.shuffle(&mut order);
+
+                let mut seen = vec![false; self.segment_lengths.len()];
+                let mut total = 0;
+                order
+                    .into_iter()
+                    .map(|group| {
+                        for &seg in self.group_segments[group].iter() {
+                            if !seen[seg] {
+                                seen[seg] = true;
+                                total += self.segment_lengths[seg];
+                            }
+                        }
+                        total
+                    })
+                    .collect()
+            })
+            .collect();
+
+        (0..groups)
+            .map(|k| {
+                let values = curves.iter().map(|curve| curve[k]);
+                let sum: usize = values.clone().sum();
+                let min = values.clone().min().unwrap_or(0);
+                let max = values.max().unwrap_or(0);
+                (sum as f64 / permutations as f64, min, max)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variants;
+    use gfa::gfa::Orientation;
+
+    fn path_data() -> PathData {
+        let path = |ids: &[usize]| -> Vec<(usize, Orientation)> {
+            ids.iter().map(|&id| (id, Orientation::Forward)).collect()
+        };
+        variants::path_data_from_steps(
+            &[(1, "AAAA"), (2, "C"), (3, "GG"), (4, "TTT")],
+            &[
+                ("a#1#chr1", &path(&[1, 2])),
+                ("a#2#chr1", &path(&[1, 3])),
+                ("b#1#chr1", &path(&[1, 2])),
+            ],
+        )
+    }
+
+    #[test]
+    fn pangenome_partition_and_growth() {
+        let path_data = path_data();
+
+        let by_path = Pangenome::from_path_data(&path_data, false);
+        let partition = by_path.partition(1.0);
+        assert_eq!(partition.core, (1, 4));
+        assert_eq!(partition.shell, (1, 1));
+        assert_eq!(partition.cloud, (1, 2));
+        assert_eq!(partition.uncovered, (1, 3));
+
+        let by_sample = Pangenome::from_path_data(&path_data, true);
+        assert_eq!(by_sample.group_names.len(), 2);
+        let partition = by_sample.partition(1.0);
+        assert_eq!(partition.core, (2, 5));
+        assert_eq!(partition.cloud, (1, 2));
+
+        assert_eq!(by_sample.segment_ids, vec![1, 2, 3, 4]);
+        assert_eq!(by_sample.segment_counts, vec![2, 2, 1, 0]);
+        assert_eq!(by_sample.part(2, 1.0), Part::Core);
+        assert_eq!(by_sample.part(1, 1.0), Part::Cloud);
+        assert_eq!(by_path.part(2, 1.0), Part::Shell);
+
+        let growth = by_path.growth(8, 1);
+        assert_eq!(growth.len(), 3);
+        assert_eq!(growth[2], (7.0, 7, 7));
+        assert!(growth[0].1 >= 5 && growth[0].2 <= 6);
+    }
+}
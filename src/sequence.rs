@@ -0,0 +1,326 @@
+use std::io::{self, BufRead, Write};
+
+use bstr::{io::*, BString, ByteSlice};
+use fnv::FnvHashMap;
+use rayon::prelude::*;
+
+use gfa::{
+    cigar::{CIGAROp, CIGAR},
+    gfa::{Segment, GFA},
+    optfields::{OptFieldVal, OptFields},
+};
+
+use crate::{subgraph, util::reverse_complement, variants::PathData};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+/// The number of bases at the start of the second segment of a link
+/// that are covered by the link's overlap.
+pub fn overlap_length(cigar: &CIGAR) -> usize {
+    use CIGAROp::*;
+    cigar
+        .iter()
+        .filter(|op| matches!(op, M | I | S | E | X))
+        .count()
+}
+
+/// The overlap lengths between consecutive steps of each path, taken
+/// from the P-line CIGARs. Paths without overlaps get an empty list.
+pub fn path_overlaps<T: OptFields>(gfa: &GFA<usize, T>) -> Vec<Vec<usize>> {
+    gfa.paths
+        .iter()
+        .map(|path| {
+            path.overlaps
+                .iter()
+                .map(|o| o.as_ref().map(overlap_length).unwrap_or(0))
+                .collect()
+        })
+        .collect()
+}
+
+/// Spell the sequence of a path, or of a 0-based, end-exclusive
+/// range of it, reverse-complementing the segments on reverse steps.
+/// The overlap between each pair of consecutive steps, from
+/// `overlaps`, is removed from the start of the second step; missing
+/// overlaps are treated as zero. Only the steps that overlap the
+/// range are spelled, and the range is clipped to the length of the
+/// path.
+pub fn path_sequence(
+    path_data: &PathData,
+    path_ix: usize,
+    overlaps: &[usize],
+    range: Option<(usize, usize)>,
+) -> Vec<u8> {
+    let path = &path_data.paths[path_ix];
+    let (start, end) = match range {
+        Some((start, end)) => (start, end.max(start)),
+        None => (0, usize::MAX),
+    };
+
+    let mut seq = Vec::new();
+    let mut pos = 0;
+
+    for (ix, &(node, _, orient)) in path.iter().enumerate() {
+        if pos >= end {
+            break;
+        }
+
+        let segment = &path_data.segment_map[&node];
+        let overlap = match ix {
+            0 => 0,
+            _ => overlaps.get(ix - 1).copied().unwrap_or(0),
+        };
+        let overlap = overlap.min(segment.len());
+        let step_end = pos + segment.len() - overlap;
+
+        if step_end > start {
+            let from = overlap + start.saturating_sub(pos);
+            let to = overlap + end.min(step_end) - pos;
+            if orient.is_reverse() {
+                let len = segment.len();
+                seq.extend(reverse_complement(&segment[len - to..len - from]));
+            } else {
+                seq.extend_from_slice(&segment[from..to]);
+            }
+        }
+
+        pos = step_end;
+    }
+
+    seq
+}
+
+/// Spell the given paths, or ranges of them, and write them as FASTA
+/// records with the given headers, in order. The paths are spelled
+/// in parallel, in batches of one path per thread, and each batch is
+/// written before the next is spelled.
+pub fn write_paths_fasta<W: Write>(
+    out: &mut W,
+    path_data: &PathData,
+    overlaps: &[Vec<usize>],
+    selection: &[(usize, Option<(usize, usize)>)],
+    headers: &[BString],
+    line_width: usize,
+) -> io::Result<()> {
+    let batch_size = rayon::current_num_threads().max(1);
+
+    for (batch, batch_headers) in
+        selection.chunks(batch_size).zip(headers.chunks(batch_size))
+    {
+        let sequences: Vec<Vec<u8>> = batch
+            .par_iter()
+            .map(|&(path_ix, range)| {
+                let path_overlaps =
+                    overlaps.get(path_ix).map(|o| o.as_slice()).unwrap_or(&[]);
+                path_sequence(path_data, path_ix, path_overlaps, range)
+            })
+            .collect();
+
+        for (header, seq) in batch_headers.iter().zip(sequences.iter()) {
+            write_fasta(out, header, seq, line_width)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Write a FASTA record, wrapping the sequence at `line_width`
+/// bases, or not at all if it's zero.
+pub fn write_fasta<W: Write>(
+    out: &mut W,
+    header: &[u8],
+    seq: &[u8],
+    line_width: usize,
+) -> io::Result<()> {
+    out.write_all(b">")?;
+    out.write_all(header)?;
+    out.write_all(b"\n")?;
+
+    if line_width == 0 {
+        out.write_all(seq)?;
+        out.write_all(b"\n")?;
+    } else {
+        for line in seq.chunks(line_width) {
+            out.write_all(line)?;
+            out.write_all(b"\n")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The FASTA header of a segment: its name, followed by its length
+/// as an `LN:i` tag if `with_length` is set, and by the given tags
+/// that it has, separated by spaces.
+pub fn segment_fasta_header<T: OptFields>(
+    segment: &Segment<BString, T>,
+    with_length: bool,
+    tags: &[Vec<u8>],
+) -> BString {
+    let mut header = segment.name.clone();
+    if with_length {
+        let len = subgraph::segment_length(segment);
+        header.extend_from_slice(format!(" LN:i:{}", len).as_bytes());
+    }
+    for tag in tags.iter() {
+        if let Some(field) = segment.optional.get_field(tag) {
+            header.extend_from_slice(format!(" {}", field).as_bytes());
+        }
+    }
+    header
+}
+
+/// Read the records of a FASTA file, as pairs of name and sequence.
+/// The name is the first word of the header line.
+pub fn read_fasta<R: BufRead>(
+    reader: R,
+) -> io::Result<Vec<(BString, Vec<u8>)>> {
+    let mut records: Vec<(BString, Vec<u8>)> = Vec::new();
+
+    for line in reader.byte_lines() {
+        let line = line?;
+        if let Some(header) = line.strip_prefix(b">") {
+            let name = header.fields().next().unwrap_or_default();
+            records.push((name.into(), Vec::new()));
+        } else if let Some((_, seq)) = records.last_mut() {
+            seq.extend_from_slice(line.trim_end());
+        } else if !line.trim().is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "FASTA sequence before the first header",
+            ));
+        }
+    }
+
+    Ok(records)
+}
+
+/// Fill in the sequences of the segments without one, those with `*`
+/// as sequence, from the given sequences by segment name. Sequences
+/// whose length doesn't match the segment's `LN` tag are not used.
+/// Returns the names of the segments that are still missing their
+/// sequence.
+pub fn attach_sequences<T: OptFields>(
+    gfa: &mut GFA<BString, T>,
+    sequences: &FnvHashMap<BString, Vec<u8>>,
+) -> Vec<BString> {
+    let mut missing = Vec::new();
+
+    for segment in gfa.segments.iter_mut() {
+        if segment.sequence.as_slice() != b"*" {
+            continue;
+        }
+
+        let seq = match sequences.get(&segment.name) {
+            Some(seq) => seq,
+            None => {
+                missing.push(segment.name.clone());
+                continue;
+            }
+        };
+
+        let length = match segment.optional.get_field(b"LN") {
+            Some(field) => match field.value {
+                OptFieldVal::Int(len) => Some(len as usize),
+                _ => None,
+            },
+            None => None,
+        };
+
+        match length {
+            Some(len) if len != seq.len() => {
+                warn!(
+                    "Sequence of segment {} has length {}, but its LN tag is {}",
+                    segment.name,
+                    seq.len(),
+                    len
+                );
+                missing.push(segment.name.clone());
+                continue;
+            }
+            _ => (),
+        }
+
+        segment.sequence = seq.as_bstr().to_owned();
+    }
+
+    missing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variants;
+
+    use gfa::gfa::Orientation::{Backward, Forward};
+
+    #[test]
+    fn spell_path_with_overlaps() {
+        let path_data = variants::path_data_from_steps(
+            &[(1, "ACGT"), (2, "GGTC"), (3, "TTA")],
+            &[("x", &[(1, Forward), (2, Backward), (3, Forward)])],
+        );
+
+        let seq = path_sequence(&path_data, 0, &[], None);
+        assert_eq!(seq, b"ACGTGACCTTA");
+
+        let seq = path_sequence(&path_data, 0, &[1], None);
+        assert_eq!(seq, b"ACGTACCTTA");
+
+        let overlaps = [0, 2];
+        let seq = path_sequence(&path_data, 0, &overlaps, Some((2, 6)));
+        assert_eq!(seq, b"GTGA");
+        let seq = path_sequence(&path_data, 0, &overlaps, Some((8, 20)));
+        assert_eq!(seq, b"A");
+        let seq = path_sequence(&path_data, 0, &overlaps, Some((20, 30)));
+        assert!(seq.is_empty());
+
+        let mut out = Vec::new();
+        write_paths_fasta(
+            &mut out,
+            &path_data,
+            &[overlaps.to_vec()],
+            &[(0, None), (0, Some((2, 6)))],
+            &["x".into(), "x:2-6".into()],
+            0,
+        )
+        .unwrap();
+        assert_eq!(out, b">x\nACGTGACCA\n>x:2-6\nGTGA\n");
+
+        let mut out = Vec::new();
+        write_fasta(&mut out, b"x", b"ACGTA", 2).unwrap();
+        assert_eq!(out, b">x\nAC\nGT\nA\n");
+    }
+
+    #[test]
+    fn attach_fasta_sequences() {
+        let fasta = b">s1 first segment\nACGT\nAC\n>s2\nGG\n>s3\nT\n";
+        let records = read_fasta(&fasta[..]).unwrap();
+        assert_eq!(records[0], (BString::from("s1"), b"ACGTAC".to_vec()));
+        assert_eq!(records.len(), 3);
+
+        let segment = |name: &str, seq: &str| Segment {
+            name: BString::from(name),
+            sequence: BString::from(seq),
+            optional: (),
+        };
+
+        let mut gfa: GFA<BString, ()> = GFA {
+            segments: vec![
+                segment("s1", "*"),
+                segment("s2", "CC"),
+                segment("s4", "*"),
+            ],
+            ..GFA::default()
+        };
+
+        let sequences: FnvHashMap<BString, Vec<u8>> =
+            records.into_iter().collect();
+        let missing = attach_sequences(&mut gfa, &sequences);
+
+        assert_eq!(missing, vec![BString::from("s4")]);
+        assert_eq!(gfa.segments[0].sequence, "ACGTAC");
+        assert_eq!(gfa.segments[1].sequence, "CC");
+    }
+}
@@ -0,0 +1,237 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use bstr::{BString, ByteSlice};
+use fnv::FnvHashMap;
+
+use gfa::{gfa::GFA, optfields::OptFields};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+fn segment_indices<T: OptFields>(
+    gfa: &GFA<BString, T>,
+) -> FnvHashMap<&[u8], usize> {
+    gfa.segments
+        .iter()
+        .enumerate()
+        .map(|(ix, s)| (s.name.as_slice(), ix))
+        .collect()
+}
+
+/// A topological order of the segments, as indices into
+/// `gfa.segments`.
+///
+/// Each link is treated as an edge from its first to its second
+/// segment, except for links between two reverse segments, which are
+/// flipped. Ties are broken by the original segment order, and cycles
+/// are broken at the first segment in the original order that hasn't
+/// been placed yet, so the order is deterministic.
+pub fn topological_order<T: OptFields>(gfa: &GFA<BString, T>) -> Vec<usize> {
+    let indices = segment_indices(gfa);
+    let count = gfa.segments.len();
+
+    let mut out_edges: Vec<Vec<usize>> = vec![Vec::new(); count];
+    let mut in_degree: Vec<usize> = vec![0; count];
+
+    for link in gfa.links.iter() {
+        let from = indices.get(link.from_segment.as_slice());
+        let to = indices.get(link.to_segment.as_slice());
+        let (from, to) = match (from, to) {
+            (Some(&from), Some(&to)) => (from, to),
+            _ => continue,
+        };
+        let (from, to) =
+            if link.from_orient.is_reverse() && link.to_orient.is_reverse() {
+                (to, from)
+            } else {
+                (from, to)
+            };
+        if from != to {
+            out_edges[from].push(to);
+            in_degree[to] += 1;
+        }
+    }
+
+    let mut heap: BinaryHeap<Reverse<usize>> = (0..count)
+        .filter(|&ix| in_degree[ix] == 0)
+        .map(Reverse)
+        .collect();
+
+    let mut placed = vec![false; count];
+    let mut order = Vec::with_capacity(count);
+    let mut next_unplaced = 0;
+    let mut cycles_broken = 0;
+
+    while order.len() < count {
+        let ix = match heap.pop() {
+            Some(Reverse(ix)) => ix,
+            None => {
+                while placed[next_unplaced] {
+                    next_unplaced += 1;
+                }
+                cycles_broken += 1;
+                next_unplaced
+            }
+        };
+
+        if placed[ix] {
+            continue;
+        }
+        placed[ix] = true;
+        order.push(ix);
+
+        for &next in out_edges[ix].iter() {
+            if !placed[next] {
+                in_degree[next] -= 1;
+                if in_degree[next] == 0 {
+                    heap.push(Reverse(next));
+                }
+            }
+        }
+    }
+
+    if cycles_broken > 0 {
+        debug!("Broke {} cycles while sorting segments", cycles_broken);
+    }
+
+    order
+}
+
+/// An order of the segments following the reference path, as
+/// indices into `gfa.segments`. Segments are placed in the order the
+/// path first visits them, followed by the segments not on the path
+/// in topological order. Returns None if the path doesn't exist.
+pub fn path_order<T: OptFields>(
+    gfa: &GFA<BString, T>,
+    path_name: &[u8],
+) -> Option<Vec<usize>> {
+    let path = gfa.paths.iter().find(|p| p.path_name == path_name)?;
+    let indices = segment_indices(gfa);
+
+    let mut placed = vec![false; gfa.segments.len()];
+    let mut order = Vec::with_capacity(gfa.segments.len());
+
+    let on_path = path
+        .iter()
+        .filter_map(|(name, _)| indices.get(name.as_bytes()));
+    let rest = topological_order(gfa);
+
+    for &ix in on_path.chain(rest.iter()) {
+        if !placed[ix] {
+            placed[ix] = true;
+            order.push(ix);
+        }
+    }
+
+    Some(order)
+}
+
+/// Reorder the segments of the graph by `order`, as returned by
+/// `topological_order` or `path_order`. Links and containments are
+/// sorted by the new positions of their segments; paths are left as
+/// they are.
+pub fn apply_order<T: OptFields>(gfa: &mut GFA<BString, T>, order: &[usize]) {
+    let mut segments: Vec<_> = std::mem::take(&mut gfa.segments)
+        .into_iter()
+        .map(Some)
+        .collect();
+    gfa.segments = order.iter().filter_map(|&ix| segments[ix].take()).collect();
+
+    let ranks: FnvHashMap<BString, usize> = gfa
+        .segments
+        .iter()
+        .enumerate()
+        .map(|(rank, s)| (s.name.clone(), rank))
+        .collect();
+    let rank = |name: &BString| ranks.get(name).copied().unwrap_or(usize::MAX);
+
+    gfa.links
+        .sort_by_key(|l| (rank(&l.from_segment), rank(&l.to_segment)));
+    gfa.containments
+        .sort_by_key(|c| (rank(&c.container_name), rank(&c.contained_name)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use gfa::gfa::{Link, Orientation, Path, Segment};
+
+    fn graph(
+        names: &[&str],
+        links: &[(&str, bool, &str, bool)],
+        path: &str,
+    ) -> GFA<BString, ()> {
+        let orient = |rev: bool| {
+            if rev {
+                Orientation::Backward
+            } else {
+                Orientation::Forward
+            }
+        };
+        GFA {
+            segments: names
+                .iter()
+                .map(|&name| Segment {
+                    name: name.into(),
+                    sequence: "A".into(),
+                    optional: (),
+                })
+                .collect(),
+            links: links
+                .iter()
+                .map(|&(from, from_rev, to, to_rev)| Link {
+                    from_segment: from.into(),
+                    from_orient: orient(from_rev),
+                    to_segment: to.into(),
+                    to_orient: orient(to_rev),
+                    overlap: "0M".into(),
+                    optional: (),
+                })
+                .collect(),
+            paths: vec![Path::new("ref".into(), path.into(), vec![], ())],
+            ..GFA::default()
+        }
+    }
+
+    fn names(gfa: &GFA<BString, ()>, order: &[usize]) -> Vec<String> {
+        order
+            .iter()
+            .map(|&ix| gfa.segments[ix].name.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn sort_segments() {
+        // d -> b -> c, with the b -> c link written as c- -> b-, and
+        // a cycle between e and a
+        let mut gfa = graph(
+            &["e", "c", "a", "b", "d"],
+            &[
+                ("d", false, "b", false),
+                ("c", true, "b", true),
+                ("e", false, "a", false),
+                ("a", false, "e", false),
+            ],
+            "a+,c+,d+",
+        );
+
+        let order = topological_order(&gfa);
+        assert_eq!(names(&gfa, &order), vec!["d", "b", "c", "e", "a"]);
+
+        let order = path_order(&gfa, b"ref").unwrap();
+        assert_eq!(names(&gfa, &order), vec!["a", "c", "d", "b", "e"]);
+        assert!(path_order(&gfa, b"missing").is_none());
+
+        apply_order(&mut gfa, &order);
+        let segments: Vec<_> =
+            gfa.segments.iter().map(|s| s.name.to_string()).collect();
+        assert_eq!(segments, vec!["a", "c", "d", "b", "e"]);
+        let links: Vec<_> = gfa
+            .links
+            .iter()
+            .map(|l| format!("{}{}", l.from_segment, l.to_segment))
+            .collect();
+        assert_eq!(links, vec!["ae", "cb", "db", "ea"]);
+    }
+}
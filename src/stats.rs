@@ -0,0 +1,266 @@
+use bstr::{BString, ByteSlice};
+use fnv::{FnvHashMap, FnvHashSet};
+use std::collections::BTreeMap;
+
+use gfa::{gfa::GFA, optfields::OptFields};
+
+use crate::{edges, subgraph};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+/// Summary statistics of a graph.
+#[derive(Debug, Default, Clone)]
+pub struct GraphStats {
+    pub segments: usize,
+    pub links: usize,
+    pub containments: usize,
+    pub paths: usize,
+    pub total_length: usize,
+    pub min_segment_length: usize,
+    pub max_segment_length: usize,
+    pub segment_n50: usize,
+    /// Number of segments with each total degree
+    pub degree_distribution: BTreeMap<usize, usize>,
+    /// Segments with links on only one side
+    pub tips: usize,
+    /// Segment sides without any links, including both sides of
+    /// isolated segments
+    pub dead_ends: usize,
+    pub self_loops: usize,
+    pub components: usize,
+    pub min_path_length: usize,
+    pub median_path_length: usize,
+    pub max_path_length: usize,
+    pub mean_path_steps: f64,
+    /// Segments visited by at least one path
+    pub covered_segments: usize,
+    /// Total length of the segments visited by at least one path
+    pub covered_length: usize,
+    /// Total length of all paths, divided by the total segment length
+    pub mean_path_depth: f64,
+}
+
+/// The N50 of the lengths: the largest length such that the lengths
+/// at least as large make up at least half of the total.
+pub fn n50(lengths: &[usize]) -> usize {
+    let mut lengths = lengths.to_vec();
+    lengths.sort_unstable_by(|a, b| b.cmp(a));
+    let total: usize = lengths.iter().sum();
+    let mut sum = 0;
+    for len in lengths {
+        sum += len;
+        if sum * 2 >= total {
+            return len;
+        }
+    }
+    0
+}
+
+pub fn graph_stats<T: OptFields>(gfa: &GFA<BString, T>) -> GraphStats {
+    let mut stats = GraphStats {
+        segments: gfa.segments.len(),
+        links: gfa.links.len(),
+        containments: gfa.containments.len(),
+        paths: gfa.paths.len(),
+        ..GraphStats::default()
+    };
+
+    let lengths: Vec<usize> =
+        gfa.segments.iter().map(subgraph::segment_length).collect();
+    let length_map: FnvHashMap<&[u8], usize> = gfa
+        .segments
+        .iter()
+        .zip(lengths.iter())
+        .map(|(s, &l)| (s.name.as_slice(), l))
+        .collect();
+
+    stats.total_length = lengths.iter().sum();
+    stats.min_segment_length = lengths.iter().copied().min().unwrap_or(0);
+    stats.max_segment_length = lengths.iter().copied().max().unwrap_or(0);
+    stats.segment_n50 = n50(&lengths);
+
+    debug!("Computing degrees");
+    for &(start, end) in edges::segment_side_degrees(gfa).iter() {
+        *stats.degree_distribution.entry(start + end).or_default() += 1;
+        match (start, end) {
+            (0, 0) => stats.dead_ends += 2,
+            (0, _) | (_, 0) => {
+                stats.tips += 1;
+                stats.dead_ends += 1;
+            }
+            _ => (),
+        }
+    }
+
+    stats.self_loops = gfa
+        .links
+        .iter()
+        .filter(|l| l.from_segment == l.to_segment)
+        .count();
+
+    debug!("Computing connected components");
+    stats.components = subgraph::connected_components(gfa).1;
+
+    debug!("Computing path statistics");
+    let mut path_lengths = Vec::with_capacity(gfa.paths.len());
+    let mut total_steps = 0;
+    let mut covered: FnvHashSet<&[u8]> = FnvHashSet::default();
+
+    for path in gfa.paths.iter() {
+        let mut path_len = 0;
+        for (seg, _) in path.iter() {
+            let seg = seg.as_bytes();
+            let len = length_map.get(seg).copied().unwrap_or(0);
+            path_len += len;
+            total_steps += 1;
+            if let Some((&name, _)) = length_map.get_key_value(seg) {
+                covered.insert(name);
+            }
+        }
+        path_lengths.push(path_len);
+    }
+
+    path_lengths.sort_unstable();
+    stats.min_path_length = path_lengths.first().copied().unwrap_or(0);
+    stats.max_path_length = path_lengths.last().copied().unwrap_or(0);
+    stats.median_path_length = path_lengths
+        .get(path_lengths.len() / 2)
+        .copied()
+        .unwrap_or(0);
+    if !gfa.paths.is_empty() {
+        stats.mean_path_steps = total_steps as f64 / gfa.paths.len() as f64;
+    }
+
+    stats.covered_segments = covered.len();
+    stats.covered_length = covered.iter().map(|s| length_map[s]).sum();
+    if stats.total_length > 0 {
+        let total_path_length: usize = path_lengths.iter().sum();
+        stats.mean_path_depth =
+            total_path_length as f64 / stats.total_length as f64;
+    }
+
+    stats
+}
+
+impl GraphStats {
+    /// The statistics as (key, value) pairs, not including the degree
+    /// distribution.
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("segments", self.segments.to_string()),
+            ("links", self.links.to_string()),
+            ("containments", self.containments.to_string()),
+            ("paths", self.paths.to_string()),
+            ("total_length", self.total_length.to_string()),
+            ("min_segment_length", self.min_segment_length.to_string()),
+            ("max_segment_length", self.max_segment_length.to_string()),
+            ("segment_n50", self.segment_n50.to_string()),
+            ("tips", self.tips.to_string()),
+            ("dead_ends", self.dead_ends.to_string()),
+            ("self_loops", self.self_loops.to_string()),
+            ("components", self.components.to_string()),
+            ("min_path_length", self.min_path_length.to_string()),
+            ("median_path_length", self.median_path_length.to_string()),
+            ("max_path_length", self.max_path_length.to_string()),
+            ("mean_path_steps", format!("{:.2}", self.mean_path_steps)),
+            ("covered_segments", self.covered_segments.to_string()),
+            ("covered_length", self.covered_length.to_string()),
+            ("mean_path_depth", format!("{:.2}", self.mean_path_depth)),
+        ]
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (key, value) in self.fields() {
+            text.push_str(&format!("{:<20}{}\n", key.replace('_', " "), value));
+        }
+        text.push_str("degree distribution\n");
+        for (degree, count) in self.degree_distribution.iter() {
+            text.push_str(&format!("  {:<18}{}\n", degree, count));
+        }
+        text
+    }
+
+    pub fn to_tsv(&self) -> String {
+        let mut tsv = String::from("#stat\tvalue\n");
+        for (key, value) in self.fields() {
+            tsv.push_str(&format!("{}\t{}\n", key, value));
+        }
+        for (degree, count) in self.degree_distribution.iter() {
+            tsv.push_str(&format!("degree_{}\t{}\n", degree, count));
+        }
+        tsv
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{");
+        for (key, value) in self.fields() {
+            json.push_str(&format!("\"{}\":{},", key, value));
+        }
+        let degrees: Vec<String> = self
+            .degree_distribution
+            .iter()
+            .map(|(degree, count)| format!("\"{}\":{}", degree, count))
+            .collect();
+        json.push_str(&format!(
+            "\"degree_distribution\":{{{}}}}}",
+            degrees.join(",")
+        ));
+        json
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gfa::gfa::{Link, Orientation, Path, Segment};
+
+    #[test]
+    fn n50_of_lengths() {
+        assert_eq!(n50(&[2, 3, 4, 5, 6, 7, 8, 9, 10]), 8);
+        assert_eq!(n50(&[10]), 10);
+        assert_eq!(n50(&[]), 0);
+    }
+
+    #[test]
+    fn stats_of_small_graph() {
+        let segment = |name: &str, seq: &str| Segment {
+            name: BString::from(name),
+            sequence: BString::from(seq),
+            optional: (),
+        };
+        let link = |from: &str, to: &str| Link {
+            from_segment: BString::from(from),
+            from_orient: Orientation::Forward,
+            to_segment: BString::from(to),
+            to_orient: Orientation::Forward,
+            overlap: "0M".into(),
+            optional: (),
+        };
+
+        // a -> b -> c, with a self loop on c, and d on its own
+        let gfa: GFA<BString, ()> = GFA {
+            segments: vec![
+                segment("a", "ACGT"),
+                segment("b", "A"),
+                segment("c", "GG"),
+                segment("d", "T"),
+            ],
+            links: vec![link("a", "b"), link("b", "c"), link("c", "c")],
+            paths: vec![Path::new("x".into(), "a+,b+,c+".into(), vec![], ())],
+            ..GFA::default()
+        };
+
+        let stats = graph_stats(&gfa);
+        assert_eq!(stats.total_length, 8);
+        assert_eq!(stats.tips, 1);
+        assert_eq!(stats.dead_ends, 3);
+        assert_eq!(stats.self_loops, 1);
+        assert_eq!(stats.components, 2);
+        let degrees: Vec<_> = stats.degree_distribution.into_iter().collect();
+        assert_eq!(degrees, vec![(0, 1), (1, 1), (2, 1), (3, 1)]);
+        assert_eq!(stats.covered_segments, 3);
+        assert_eq!(stats.max_path_length, 7);
+    }
+}
@@ -1,9 +1,18 @@
-use gfa::gfa::GFA;
-use gfa::optfields::OptFields;
+pub mod streaming;
 
-use std::collections::HashSet;
+use gfa::gfa::{Orientation, Path, Segment, GFA};
+use gfa::optfields::{OptFieldVal, OptFields};
 
-use bstr::BString;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use bstr::{BStr, BString, ByteSlice};
+use regex::bytes::Regex;
+
+use crate::util::pansn_sample_haplotype;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
 
 macro_rules! filtered {
     ($coll:expr, $pred:expr) => {
@@ -11,51 +20,186 @@ macro_rules! filtered {
     };
 }
 
-/// Build a new GFA consisting of subgraphs of the given GFA that only
-/// include segments that are in the provided paths. Paths are to be
-/// provided as a slice of path names
-pub fn paths_new_subgraph<T: OptFields + Clone>(
+/// Chooses paths by name. A path is chosen if it has one of the
+/// given names, or matches one of the patterns, and, if a PanSN
+/// sample or haplotype is given, belongs to that sample and
+/// haplotype. If there are no names or patterns, only the sample and
+/// haplotype are used.
+#[derive(Debug, Default, Clone)]
+pub struct PathFilter {
+    pub names: HashSet<Vec<u8>>,
+    pub patterns: Vec<Regex>,
+    pub sample: Option<Vec<u8>>,
+    pub haplotype: Option<Vec<u8>>,
+}
+
+impl PathFilter {
+    pub fn from_names(names: &[Vec<u8>]) -> Self {
+        Self {
+            names: names.iter().cloned().collect(),
+            ..Self::default()
+        }
+    }
+
+    pub fn matches(&self, name: &[u8]) -> bool {
+        let by_name = (self.names.is_empty() && self.patterns.is_empty())
+            || self.names.contains(name)
+            || self.patterns.iter().any(|re| re.is_match(name));
+
+        if !by_name {
+            return false;
+        }
+
+        if self.sample.is_none() && self.haplotype.is_none() {
+            return true;
+        }
+
+        match pansn_sample_haplotype(name) {
+            Some((sample, haplotype)) => {
+                self.sample.as_ref().is_none_or(|s| s == sample)
+                    && self
+                        .haplotype
+                        .as_ref()
+                        .is_none_or(|h| Some(h.as_slice()) == haplotype)
+            }
+            None => false,
+        }
+    }
+}
+
+/// Translate a glob, where `*` matches any number of characters and
+/// `?` matches one, into a regex matching the whole name.
+pub fn glob_regex(glob: &str) -> Result<Regex, regex::Error> {
+    let mut pattern = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    Regex::new(&pattern)
+}
+
+/// Build a new GFA with the paths chosen by the filter, and the
+/// segments they visit. If `exclude` is true, the chosen paths are
+/// removed instead, along with the segments that only they visit.
+pub fn path_filter_subgraph<T: OptFields + Clone>(
     gfa: &GFA<BString, T>,
-    paths: &[Vec<u8>],
+    filter: &PathFilter,
+    exclude: bool,
 ) -> GFA<BString, T> {
-    let path_names: HashSet<&[u8]> = paths.iter().map(|p| p.as_ref()).collect();
+    let (chosen, rest): (Vec<_>, Vec<_>) = gfa
+        .paths
+        .iter()
+        .cloned()
+        .partition(|p| filter.matches(p.path_name.as_slice()));
 
-    // Filter out the paths in the GFA we don't want
-    let paths: Vec<_> =
-        filtered!(gfa.paths, |p| path_names.contains(p.path_name.as_slice()));
+    debug!("{} paths match the filter", chosen.len());
 
-    // Set of the segments in the paths we're keeping
-    let segment_names: HashSet<&[u8]> = paths
+    let path_segments = |paths: &[Path<BString, T>]| -> HashSet<Vec<u8>> {
+        paths
+            .iter()
+            .flat_map(|path| path.iter().map(|(seg, _)| seg.to_vec()))
+            .collect()
+    };
+
+    if !exclude {
+        let segments = path_segments(&chosen);
+        let segment_names = segments.iter().map(|s| s.as_slice()).collect();
+        return induced_subgraph(gfa, &segment_names, chosen);
+    }
+
+    let removed = path_segments(&chosen);
+    let kept = path_segments(&rest);
+
+    let segment_names: HashSet<&[u8]> = gfa
+        .segments
         .iter()
-        .flat_map(|path| path.iter().map(|(seg, _)| seg.as_ref()))
+        .map(|s| s.name.as_slice())
+        .filter(|s| !removed.contains(*s) || kept.contains(*s))
         .collect();
 
-    // Filter out the segments in the GFA we don't want
-    let segments =
-        filtered!(gfa.segments, |s| segment_names.contains(s.name.as_slice()));
+    induced_subgraph(gfa, &segment_names, rest)
+}
 
-    // Filter out the links in the GFA we don't want
-    let links = filtered!(&gfa.links, |l| {
-        segment_names.contains(l.from_segment.as_slice())
-            && segment_names.contains(l.to_segment.as_slice())
-    });
+/// How to choose segments by which paths visit them.
+#[derive(Debug, Clone)]
+pub enum SetOperation {
+    /// Segments visited by every chosen path.
+    Core,
+    /// Segments visited by exactly one path in the graph, which is
+    /// one of the chosen paths.
+    Private,
+    /// Segments visited by a chosen path, but by none of the paths
+    /// matching the given filter.
+    Difference(PathFilter),
+}
 
-    let containments = filtered!(&gfa.containments, |l| {
-        segment_names.contains(l.container_name.as_slice())
-            && segment_names.contains(l.contained_name.as_slice())
-    });
+/// Find the segments chosen by the set operation over the paths
+/// matching `chosen`, in the order they appear in the GFA.
+pub fn path_set_segments<T: OptFields>(
+    gfa: &GFA<BString, T>,
+    chosen: &PathFilter,
+    operation: &SetOperation,
+) -> Vec<Vec<u8>> {
+    #[derive(Default, Clone, Copy)]
+    struct Membership {
+        chosen: usize,
+        paths: usize,
+        minus: bool,
+    }
 
-    GFA {
-        header: gfa.header.clone(),
-        segments,
-        links,
-        paths,
-        containments,
+    let mut memberships: HashMap<&[u8], Membership> = HashMap::new();
+    let mut chosen_count = 0;
+
+    for path in gfa.paths.iter() {
+        let name = path.path_name.as_slice();
+        let is_chosen = chosen.matches(name);
+        let is_minus = match operation {
+            SetOperation::Difference(minus) => minus.matches(name),
+            _ => false,
+        };
+        if is_chosen {
+            chosen_count += 1;
+        }
+
+        let segments: HashSet<&[u8]> =
+            path.iter().map(|(seg, _)| seg.as_bytes()).collect();
+
+        for seg in segments {
+            let entry = memberships.entry(seg).or_default();
+            entry.paths += 1;
+            if is_chosen {
+                entry.chosen += 1;
+            }
+            if is_minus {
+                entry.minus = true;
+            }
+        }
     }
+
+    debug!("{} paths match the filter", chosen_count);
+
+    let keep = |m: &Membership| match operation {
+        SetOperation::Core => chosen_count > 0 && m.chosen == chosen_count,
+        SetOperation::Private => m.paths == 1 && m.chosen == 1,
+        SetOperation::Difference(_) => m.chosen > 0 && !m.minus,
+    };
+
+    gfa.segments
+        .iter()
+        .filter(|s| memberships.get(s.name.as_slice()).is_some_and(keep))
+        .map(|s| s.name.to_vec())
+        .collect()
 }
 
 /// Returns a subgraph GFA that only contains elements with the
-/// provided segment names
+/// provided segment names. Paths are cut into the contiguous pieces
+/// that lie inside the subgraph, each named with the subrange it
+/// covers; paths that lie entirely inside the subgraph are kept as
+/// they are.
 pub fn segments_subgraph<T: OptFields + Clone>(
     gfa: &GFA<BString, T>,
     segment_names: &[Vec<u8>],
@@ -63,6 +207,359 @@ pub fn segments_subgraph<T: OptFields + Clone>(
     let segment_names: HashSet<&[u8]> =
         segment_names.iter().map(|s| s.as_ref()).collect();
 
+    let lengths: HashMap<&[u8], usize> = gfa
+        .segments
+        .iter()
+        .map(|s| (s.name.as_slice(), segment_length(s)))
+        .collect();
+
+    let mut paths = Vec::new();
+
+    for path in gfa.paths.iter() {
+        let steps = PathSteps::new(path, &lengths);
+        let pieces = path_pieces(&steps, &segment_names);
+
+        if pieces.len() == 1 && pieces[0] == (0..steps.steps.len()) {
+            paths.push(path.clone());
+        } else {
+            paths.extend(
+                pieces
+                    .into_iter()
+                    .map(|range| sub_path(path, &steps, range)),
+            );
+        }
+    }
+
+    induced_subgraph(gfa, &segment_names, paths)
+}
+
+/// The maximal ranges of consecutive steps of a path whose segments
+/// are all in `segment_names`.
+pub fn path_pieces(
+    path_steps: &PathSteps<'_>,
+    segment_names: &HashSet<&[u8]>,
+) -> Vec<std::ops::Range<usize>> {
+    let mut pieces = Vec::new();
+    let mut start = None;
+
+    for (ix, (seg, _)) in path_steps.steps.iter().enumerate() {
+        let inside = segment_names.contains(seg.as_bytes());
+        match (inside, start) {
+            (true, None) => start = Some(ix),
+            (false, Some(s)) => {
+                pieces.push(s..ix);
+                start = None;
+            }
+            _ => (),
+        }
+    }
+
+    if let Some(s) = start {
+        pieces.push(s..path_steps.steps.len());
+    }
+
+    pieces
+}
+
+/// How far around the seed segments a neighborhood extends, either
+/// in number of links, or in base pairs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Context {
+    Steps(usize),
+    Bases(usize),
+}
+
+/// The length of a segment, using the LN tag if the sequence is
+/// missing.
+pub fn segment_length<T: OptFields>(seg: &Segment<BString, T>) -> usize {
+    if seg.sequence.as_slice() != b"*" {
+        return seg.sequence.len();
+    }
+    match seg.optional.get_field(b"LN").map(|f| &f.value) {
+        Some(OptFieldVal::Int(len)) => *len as usize,
+        _ => 0,
+    }
+}
+
+/// Expand the seed segments to every segment within the given
+/// context, following links in both directions, like `vg find -c`.
+///
+/// With `Context::Steps(n)`, segments at most `n` links away from a
+/// seed are included. With `Context::Bases(n)`, a segment is included
+/// if there are at most `n` bases of other segments between it and
+/// the closest seed.
+pub fn segment_neighborhood<T: OptFields>(
+    gfa: &GFA<BString, T>,
+    seeds: &[Vec<u8>],
+    context: Context,
+) -> Vec<Vec<u8>> {
+    let lengths: HashMap<&[u8], usize> = gfa
+        .segments
+        .iter()
+        .map(|s| (s.name.as_slice(), segment_length(s)))
+        .collect();
+
+    let mut neighbors: HashMap<&[u8], Vec<&[u8]>> = HashMap::new();
+    for link in gfa.links.iter() {
+        let from = link.from_segment.as_slice();
+        let to = link.to_segment.as_slice();
+        neighbors.entry(from).or_default().push(to);
+        neighbors.entry(to).or_default().push(from);
+    }
+
+    let mut seed_names: HashSet<&[u8]> = HashSet::new();
+    let mut distances: HashMap<&[u8], usize> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+
+    for seed in seeds.iter() {
+        let seed = seed.as_slice();
+        if !lengths.contains_key(seed) {
+            warn!("Segment {} not found in graph", seed.as_bstr());
+            continue;
+        }
+        seed_names.insert(seed);
+        distances.insert(seed, 0);
+        queue.push(Reverse((0, seed)));
+    }
+
+    let limit = match context {
+        Context::Steps(n) | Context::Bases(n) => n,
+    };
+
+    // Dijkstra from all seeds at once, where leaving a segment costs
+    // one step, or its length in bases if it isn't a seed
+    while let Some(Reverse((dist, seg))) = queue.pop() {
+        if distances.get(seg).is_some_and(|&d| d < dist) {
+            continue;
+        }
+        let cost = match context {
+            Context::Steps(_) => 1,
+            Context::Bases(_) if seed_names.contains(seg) => 0,
+            Context::Bases(_) => lengths.get(seg).copied().unwrap_or(0),
+        };
+        let next_dist = dist + cost;
+        if next_dist > limit {
+            continue;
+        }
+        for &next in neighbors.get(seg).into_iter().flatten() {
+            let is_closer = distances.get(next).is_none_or(|&d| next_dist < d);
+            if is_closer {
+                distances.insert(next, next_dist);
+                queue.push(Reverse((next_dist, next)));
+            }
+        }
+    }
+
+    debug!(
+        "Expanded {} seed segments to {} segments",
+        seed_names.len(),
+        distances.len()
+    );
+
+    distances.keys().map(|s| s.to_vec()).collect()
+}
+
+/// The steps of a path, along with the offset of each step, and the
+/// total length of the path as the last offset. Offsets are 0-based.
+pub struct PathSteps<'a> {
+    pub steps: Vec<(&'a BStr, Orientation)>,
+    pub offsets: Vec<usize>,
+}
+
+impl<'a> PathSteps<'a> {
+    pub fn new<T: OptFields>(
+        path: &'a Path<BString, T>,
+        lengths: &HashMap<&[u8], usize>,
+    ) -> Self {
+        let steps: Vec<_> = path.iter().collect();
+        let mut offsets = Vec::with_capacity(steps.len() + 1);
+        let mut offset = 0;
+        offsets.push(offset);
+        for (seg, _) in steps.iter() {
+            offset += lengths.get(seg.as_bytes()).copied().unwrap_or(0);
+            offsets.push(offset);
+        }
+        Self { steps, offsets }
+    }
+
+    /// The range of steps overlapping the bases `start..end`, if any.
+    pub fn step_range(
+        &self,
+        start: usize,
+        end: usize,
+    ) -> Option<std::ops::Range<usize>> {
+        let first = self.offsets[1..].iter().position(|&o| o > start)?;
+        let last = self.offsets[..self.steps.len()]
+            .iter()
+            .rposition(|&o| o < end)?;
+        if first > last {
+            return None;
+        }
+        Some(first..last + 1)
+    }
+}
+
+/// Build the part of `path` consisting of the steps in `range`, named
+/// with the base range it covers, as `name:start-end`. The overlaps
+/// are trimmed to match, if there's one per link in the path.
+pub fn sub_path<T: OptFields>(
+    path: &Path<BString, T>,
+    path_steps: &PathSteps<'_>,
+    range: std::ops::Range<usize>,
+) -> Path<BString, T> {
+    let name = format!(
+        "{}:{}-{}",
+        path.path_name,
+        path_steps.offsets[range.start],
+        path_steps.offsets[range.end]
+    );
+
+    let mut segment_names = BString::from(Vec::new());
+    for (ix, (seg, orient)) in
+        path_steps.steps[range.clone()].iter().enumerate()
+    {
+        if ix > 0 {
+            segment_names.push(b',');
+        }
+        segment_names.extend_from_slice(seg.as_bytes());
+        segment_names.push(if orient.is_reverse() { b'-' } else { b'+' });
+    }
+
+    let overlaps = if path.overlaps.len() + 1 == path_steps.steps.len() {
+        path.overlaps[range.start..range.end - 1].to_vec()
+    } else {
+        Vec::new()
+    };
+
+    Path::new(
+        BString::from(name),
+        segment_names,
+        overlaps,
+        path.optional.clone(),
+    )
+}
+
+/// Build the subgraph covering each of the regions, given as (path
+/// name, start, end) with 0-based, end-exclusive coordinates, like in
+/// BED files.
+///
+/// The path steps overlapping a region decide the boundary segments
+/// of the region. Besides the segments on the region's path, the
+/// segments that other paths visit between the two boundary segments
+/// are included, so that alternative alleles are kept. Each path is
+/// trimmed to the parts that lie in a region, named with the
+/// subrange they cover.
+pub fn region_subgraph<T: OptFields + Clone>(
+    gfa: &GFA<BString, T>,
+    regions: &[(BString, usize, usize)],
+) -> GFA<BString, T> {
+    let lengths: HashMap<&[u8], usize> = gfa
+        .segments
+        .iter()
+        .map(|s| (s.name.as_slice(), segment_length(s)))
+        .collect();
+
+    let path_steps: Vec<PathSteps<'_>> = gfa
+        .paths
+        .iter()
+        .map(|path| PathSteps::new(path, &lengths))
+        .collect();
+
+    let mut segment_names: HashSet<&[u8]> = HashSet::new();
+    let mut pieces = Vec::new();
+
+    for (name, start, end) in regions.iter() {
+        let path_ix = match gfa.paths.iter().position(|p| &p.path_name == name)
+        {
+            Some(ix) => ix,
+            None => {
+                warn!("Path {} not found in graph", name);
+                continue;
+            }
+        };
+
+        let steps = &path_steps[path_ix];
+        let range = match steps.step_range(*start, *end) {
+            Some(range) => range,
+            None => {
+                warn!("Region {}:{}-{} is outside the path", name, start, end);
+                continue;
+            }
+        };
+
+        let first = steps.steps[range.start].0.as_bytes();
+        let last = steps.steps[range.end - 1].0.as_bytes();
+
+        segment_names.extend(
+            steps.steps[range.clone()].iter().map(|(s, _)| s.as_bytes()),
+        );
+        pieces.push((path_ix, range));
+
+        // Other traversals between the boundary segments, in either
+        // direction
+        for (other_ix, other) in path_steps.iter().enumerate() {
+            let boundary_steps: Vec<usize> = other
+                .steps
+                .iter()
+                .enumerate()
+                .filter(|(_, (s, _))| {
+                    s.as_bytes() == first || s.as_bytes() == last
+                })
+                .map(|(ix, _)| ix)
+                .collect();
+
+            if first == last {
+                // The region lies in a single segment
+                pieces.extend(
+                    boundary_steps.iter().map(|&a| (other_ix, a..a + 1)),
+                );
+                continue;
+            }
+
+            for pair in boundary_steps.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                if other.steps[a].0 == other.steps[b].0 {
+                    continue;
+                }
+                segment_names.extend(
+                    other.steps[a..=b].iter().map(|(s, _)| s.as_bytes()),
+                );
+                pieces.push((other_ix, a..b + 1));
+            }
+        }
+    }
+
+    // Merge the overlapping pieces of each path, so that no step is
+    // in more than one output path
+    pieces.sort_by_key(|(path_ix, range)| (*path_ix, range.start));
+    let mut kept: Vec<(usize, std::ops::Range<usize>)> = Vec::new();
+    for (path_ix, range) in pieces {
+        if let Some((prev_ix, prev)) = kept.last_mut() {
+            if *prev_ix == path_ix && range.start < prev.end {
+                prev.end = prev.end.max(range.end);
+                continue;
+            }
+        }
+        kept.push((path_ix, range));
+    }
+
+    let paths = kept
+        .into_iter()
+        .map(|(path_ix, range)| {
+            sub_path(&gfa.paths[path_ix], &path_steps[path_ix], range)
+        })
+        .collect();
+
+    induced_subgraph(gfa, &segment_names, paths)
+}
+
+/// Build the subgraph with the given segments, the links and
+/// containments between them, and the given paths.
+fn induced_subgraph<T: OptFields + Clone>(
+    gfa: &GFA<BString, T>,
+    segment_names: &HashSet<&[u8]>,
+    paths: Vec<Path<BString, T>>,
+) -> GFA<BString, T> {
     let segments =
         filtered!(gfa.segments, |s| segment_names.contains(s.name.as_slice()));
 
@@ -76,10 +573,6 @@ pub fn segments_subgraph<T: OptFields + Clone>(
             && segment_names.contains(l.contained_name.as_slice())
     });
 
-    let paths: Vec<_> = filtered!(gfa.paths, |p| p
-        .iter()
-        .any(|(s, _)| segment_names.contains(s.as_ref())));
-
     GFA {
         header: gfa.header.clone(),
         segments,
@@ -88,3 +581,373 @@ pub fn segments_subgraph<T: OptFields + Clone>(
         containments,
     }
 }
+
+/// Assign each segment to a weakly connected component, following
+/// links and containments. Returns the component index of each
+/// segment, in the order of `gfa.segments`, along with the number of
+/// components. Components are numbered in the order their first
+/// segment appears.
+pub fn connected_components<T: OptFields>(
+    gfa: &GFA<BString, T>,
+) -> (Vec<usize>, usize) {
+    let indices: HashMap<&[u8], usize> = gfa
+        .segments
+        .iter()
+        .enumerate()
+        .map(|(ix, s)| (s.name.as_slice(), ix))
+        .collect();
+
+    let mut parents: Vec<usize> = (0..gfa.segments.len()).collect();
+
+    fn find(parents: &mut [usize], mut ix: usize) -> usize {
+        while parents[ix] != ix {
+            parents[ix] = parents[parents[ix]];
+            ix = parents[ix];
+        }
+        ix
+    }
+
+    let edges = gfa
+        .links
+        .iter()
+        .map(|l| (&l.from_segment, &l.to_segment))
+        .chain(
+            gfa.containments
+                .iter()
+                .map(|c| (&c.container_name, &c.contained_name)),
+        );
+
+    for (from, to) in edges {
+        let from = indices.get(from.as_slice());
+        let to = indices.get(to.as_slice());
+        if let (Some(&from), Some(&to)) = (from, to) {
+            let from = find(&mut parents, from);
+            let to = find(&mut parents, to);
+            if from != to {
+                parents[from.max(to)] = from.min(to);
+            }
+        }
+    }
+
+    let mut component_ids: HashMap<usize, usize> = HashMap::new();
+    let components = (0..gfa.segments.len())
+        .map(|ix| {
+            let root = find(&mut parents, ix);
+            let next_id = component_ids.len();
+            *component_ids.entry(root).or_insert(next_id)
+        })
+        .collect();
+
+    (components, component_ids.len())
+}
+
+/// Split the graph into its weakly connected components. Each path is
+/// placed in the component of its segments; a path that crosses
+/// between components, which can only happen if it uses a step without
+/// a link, is split into one piece per run of steps in the same
+/// component, named `name:start-end` like in `region_subgraph`.
+/// Steps on missing segments are dropped.
+pub fn split_components<T: OptFields + Clone>(
+    gfa: &GFA<BString, T>,
+) -> Vec<GFA<BString, T>> {
+    let (components, count) = connected_components(gfa);
+
+    let segment_components: HashMap<&[u8], usize> = gfa
+        .segments
+        .iter()
+        .zip(components.iter())
+        .map(|(s, &c)| (s.name.as_slice(), c))
+        .collect();
+
+    let mut graphs: Vec<GFA<BString, T>> = (0..count)
+        .map(|_| GFA {
+            header: gfa.header.clone(),
+            segments: Vec::new(),
+            links: Vec::new(),
+            paths: Vec::new(),
+            containments: Vec::new(),
+        })
+        .collect();
+
+    for (seg, &comp) in gfa.segments.iter().zip(components.iter()) {
+        graphs[comp].segments.push(seg.clone());
+    }
+
+    for link in gfa.links.iter() {
+        if let Some(&comp) =
+            segment_components.get(link.from_segment.as_slice())
+        {
+            graphs[comp].links.push(link.clone());
+        }
+    }
+
+    for cont in gfa.containments.iter() {
+        let comp = segment_components.get(cont.container_name.as_slice());
+        if let Some(&comp) = comp {
+            graphs[comp].containments.push(cont.clone());
+        }
+    }
+
+    let lengths: HashMap<&[u8], usize> = gfa
+        .segments
+        .iter()
+        .map(|s| (s.name.as_slice(), segment_length(s)))
+        .collect();
+
+    for path in gfa.paths.iter() {
+        let steps = PathSteps::new(path, &lengths);
+        let step_components: Vec<Option<usize>> = steps
+            .steps
+            .iter()
+            .map(|(seg, _)| segment_components.get(seg.as_bytes()).copied())
+            .collect();
+
+        // The maximal runs of steps in the same component
+        let mut runs: Vec<(usize, std::ops::Range<usize>)> = Vec::new();
+        for (ix, comp) in step_components.iter().copied().enumerate() {
+            match (comp, runs.last_mut()) {
+                (Some(comp), Some((prev, range)))
+                    if *prev == comp && range.end == ix =>
+                {
+                    range.end = ix + 1;
+                }
+                (Some(comp), _) => runs.push((comp, ix..ix + 1)),
+                (None, _) => (),
+            }
+        }
+
+        match runs.as_slice() {
+            [] => warn!("Path {} has no segments in the graph", path.path_name),
+            [(comp, range)] if range.len() == steps.steps.len() => {
+                graphs[*comp].paths.push(path.clone());
+            }
+            _ => {
+                warn!(
+                    "Path {} is split into {} pieces across components",
+                    path.path_name,
+                    runs.len()
+                );
+                for (comp, range) in runs {
+                    graphs[comp].paths.push(sub_path(path, &steps, range));
+                }
+            }
+        }
+    }
+
+    graphs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gfa::gfa::{Link, Orientation};
+
+    fn linear_gfa(seqs: &[&str]) -> GFA<BString, ()> {
+        let segments = seqs
+            .iter()
+            .enumerate()
+            .map(|(ix, seq)| Segment {
+                name: BString::from((ix + 1).to_string()),
+                sequence: BString::from(*seq),
+                optional: (),
+            })
+            .collect();
+        let links = (1..seqs.len())
+            .map(|ix| Link {
+                from_segment: BString::from(ix.to_string()),
+                from_orient: Orientation::Forward,
+                to_segment: BString::from((ix + 1).to_string()),
+                to_orient: Orientation::Forward,
+                overlap: "0M".into(),
+                optional: (),
+            })
+            .collect();
+        GFA {
+            segments,
+            links,
+            ..GFA::default()
+        }
+    }
+
+    fn sorted(mut names: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn neighborhood_by_steps_and_bases() {
+        let gfa = linear_gfa(&["AAAA", "C", "GGGGGG", "T", "AA"]);
+        let seeds = vec![b"3".to_vec()];
+
+        let by_steps = segment_neighborhood(&gfa, &seeds, Context::Steps(1));
+        assert_eq!(
+            sorted(by_steps),
+            vec![b"2".to_vec(), b"3".to_vec(), b"4".to_vec()]
+        );
+
+        // Segment 1 is one base (segment 2) away, segment 5 is one
+        // base (segment 4) away
+        let by_bases = segment_neighborhood(&gfa, &seeds, Context::Bases(1));
+        assert_eq!(by_bases.len(), 5);
+
+        let by_bases = segment_neighborhood(&gfa, &seeds, Context::Bases(0));
+        assert_eq!(
+            sorted(by_bases),
+            vec![b"2".to_vec(), b"3".to_vec(), b"4".to_vec()]
+        );
+    }
+
+    #[test]
+    fn steps_overlapping_region() {
+        let steps = PathSteps {
+            steps: ["1", "2", "3", "4"]
+                .iter()
+                .map(|s| (s.as_bytes().as_bstr(), Orientation::Forward))
+                .collect(),
+            offsets: vec![0, 4, 5, 11, 12],
+        };
+
+        assert_eq!(steps.step_range(0, 4), Some(0..1));
+        assert_eq!(steps.step_range(3, 6), Some(0..3));
+        assert_eq!(steps.step_range(4, 5), Some(1..2));
+        assert_eq!(steps.step_range(11, 20), Some(3..4));
+        assert_eq!(steps.step_range(12, 20), None);
+    }
+
+    #[test]
+    fn pieces_inside_segment_set() {
+        let steps = PathSteps {
+            steps: ["1", "2", "3", "4", "5", "2"]
+                .iter()
+                .map(|s| (s.as_bytes().as_bstr(), Orientation::Forward))
+                .collect(),
+            offsets: vec![0, 1, 2, 3, 4, 5, 6],
+        };
+
+        let names: HashSet<&[u8]> =
+            [&b"2"[..], b"3", b"5"].iter().copied().collect();
+        assert_eq!(path_pieces(&steps, &names), vec![1..3, 4..6]);
+
+        let names: HashSet<&[u8]> = HashSet::new();
+        assert!(path_pieces(&steps, &names).is_empty());
+    }
+
+    #[test]
+    fn overlapping_regions_are_merged() {
+        let mut gfa = linear_gfa(&["A", "C", "G", "T", "A", "C"]);
+        gfa.paths = vec![Path::new(
+            "ref".into(),
+            "1+,2+,3+,4+,5+,6+".into(),
+            vec![],
+            (),
+        )];
+
+        // Steps 0..3 and 2..5 overlap in step 2
+        let regions = vec![("ref".into(), 0, 3), ("ref".into(), 2, 5)];
+        let sub = region_subgraph(&gfa, &regions);
+
+        assert_eq!(sub.segments.len(), 5);
+        assert_eq!(sub.paths.len(), 1);
+        assert_eq!(sub.paths[0].path_name, "ref:0-5");
+        assert_eq!(sub.paths[0].segment_names, "1+,2+,3+,4+,5+");
+    }
+
+    #[test]
+    fn components_follow_links() {
+        let mut gfa = linear_gfa(&["A", "C", "G", "T", "A"]);
+        // Split into 1-2 and 3-4-5
+        gfa.links.remove(1);
+        gfa.paths = vec![
+            Path::new("x".into(), "1+,2+,3+,4+".into(), vec![], ()),
+            Path::new("y".into(), "4+,5+".into(), vec![], ()),
+        ];
+
+        let (components, count) = connected_components(&gfa);
+        assert_eq!(count, 2);
+        assert_eq!(components, vec![0, 0, 1, 1, 1]);
+
+        let graphs = split_components(&gfa);
+        assert_eq!(graphs[0].segments.len(), 2);
+        assert_eq!(graphs[0].links.len(), 1);
+        assert_eq!(graphs[1].segments.len(), 3);
+        assert_eq!(graphs[1].links.len(), 2);
+
+        let paths = |gfa: &GFA<BString, ()>| -> Vec<(String, String)> {
+            gfa.paths
+                .iter()
+                .map(|p| (p.path_name.to_string(), p.segment_names.to_string()))
+                .collect()
+        };
+        // x crosses the missing link between 2 and 3
+        assert_eq!(
+            paths(&graphs[0]),
+            vec![("x:0-2".to_string(), "1+,2+".to_string())]
+        );
+        assert_eq!(
+            paths(&graphs[1]),
+            vec![
+                ("x:2-4".to_string(), "3+,4+".to_string()),
+                ("y".to_string(), "4+,5+".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn path_filter_matching() {
+        let mut filter = PathFilter::default();
+        filter.patterns.push(glob_regex("HG00*#1#chr?").unwrap());
+        assert!(filter.matches(b"HG002#1#chr1"));
+        assert!(!filter.matches(b"HG002#1#chr10"));
+        assert!(!filter.matches(b"HG002#2#chr1"));
+
+        let filter = PathFilter {
+            sample: Some(b"HG002".to_vec()),
+            haplotype: Some(b"2".to_vec()),
+            ..PathFilter::default()
+        };
+        assert!(filter.matches(b"HG002#2#chr1"));
+        assert!(!filter.matches(b"HG002#1#chr1"));
+        assert!(!filter.matches(b"HG003#2#chr1"));
+        assert!(!filter.matches(b"chr1"));
+    }
+
+    #[test]
+    fn path_set_operations() {
+        let mut gfa = linear_gfa(&["A", "C", "G", "T", "A"]);
+        let path = |name: &str, segs: &str| {
+            Path::new(BString::from(name), BString::from(segs), Vec::new(), ())
+        };
+        gfa.paths = vec![
+            path("a", "1+,2+,3+,5+"),
+            path("b", "1+,3+,4+,5+"),
+            path("c", "1+,5+"),
+        ];
+
+        let names = |ns: &[&str]| {
+            PathFilter::from_names(
+                &ns.iter().map(|n| n.as_bytes().to_vec()).collect::<Vec<_>>(),
+            )
+        };
+        let segs = |ss: &[&str]| {
+            ss.iter().map(|s| s.as_bytes().to_vec()).collect::<Vec<_>>()
+        };
+
+        let core =
+            path_set_segments(&gfa, &names(&["a", "b"]), &SetOperation::Core);
+        assert_eq!(core, segs(&["1", "3", "5"]));
+
+        let private = path_set_segments(
+            &gfa,
+            &names(&["a", "b", "c"]),
+            &SetOperation::Private,
+        );
+        assert_eq!(private, segs(&["2", "4"]));
+
+        let diff = path_set_segments(
+            &gfa,
+            &names(&["a"]),
+            &SetOperation::Difference(names(&["b"])),
+        );
+        assert_eq!(diff, segs(&["2"]));
+    }
+}
@@ -0,0 +1,460 @@
+use bstr::{io::*, BString, ByteSlice};
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{self, BufReader, Write},
+    path::Path,
+};
+
+use super::PathFilter;
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+/// What to extract from the GFA.
+#[derive(Debug, Clone)]
+pub enum Selection {
+    /// Whole paths, and the segments they visit. Walks are matched by
+    /// their `sample#haplotype#sequence` name.
+    Paths(PathFilter),
+    /// Segments, with the paths and walks trimmed to the pieces that
+    /// visit only the given segments.
+    Segments(Vec<Vec<u8>>),
+}
+
+/// The result of the first pass over the GFA, deciding which lines to
+/// keep in the second pass.
+#[derive(Debug, Default, Clone)]
+pub struct LineFilter {
+    segments: HashSet<BString>,
+    paths: Option<PathFilter>,
+    lengths: HashMap<BString, usize>,
+}
+
+fn byte_lines<P: AsRef<Path>>(
+    gfa_path: P,
+) -> io::Result<impl Iterator<Item = io::Result<Vec<u8>>>> {
+    let file = File::open(gfa_path.as_ref())?;
+    Ok(BufReader::new(file).byte_lines())
+}
+
+/// The segment names in the steps field of a P-line, such as
+/// `1+,2-,3+`.
+pub(crate) fn path_line_steps(
+    steps: &[u8],
+) -> impl Iterator<Item = (&[u8], u8)> {
+    steps.split_str(",").filter(|s| !s.is_empty()).map(|step| {
+        let (name, orient) = step.split_at(step.len() - 1);
+        (name, orient[0])
+    })
+}
+
+/// The segment names in the walk field of a W-line, such as
+/// `>1<2>3`.
+pub(crate) fn walk_line_steps(walk: &[u8]) -> Vec<(&[u8], u8)> {
+    let mut steps = Vec::new();
+    let mut start = 0;
+    for ix in 1..=walk.len() {
+        if ix == walk.len() || walk[ix] == b'>' || walk[ix] == b'<' {
+            steps.push((&walk[start + 1..ix], walk[start]));
+            start = ix;
+        }
+    }
+    steps
+}
+
+fn walk_name(fields: &[&[u8]]) -> BString {
+    let mut name = BString::from(fields[1]);
+    name.push(b'#');
+    name.extend_from_slice(fields[2]);
+    name.push(b'#');
+    name.extend_from_slice(fields[3]);
+    name
+}
+
+impl LineFilter {
+    /// A filter keeping the given segments, and the pieces of the
+    /// paths and walks that lie inside them. `lengths` must contain
+    /// the length of every segment up to the last kept step of each
+    /// path, to name the path pieces.
+    pub fn from_segments(
+        segments: HashSet<BString>,
+        lengths: HashMap<BString, usize>,
+    ) -> Self {
+        Self {
+            segments,
+            paths: None,
+            lengths,
+        }
+    }
+
+    /// A filter keeping the given segments, and the given paths and
+    /// walks in full.
+    pub fn from_paths(segments: HashSet<BString>, paths: PathFilter) -> Self {
+        Self {
+            segments,
+            paths: Some(paths),
+            lengths: HashMap::new(),
+        }
+    }
+
+    /// Read the GFA to find the segments to keep. When selecting
+    /// segments, the GFA is read twice, as the lengths of the segments
+    /// the paths visit before their last kept step are needed too.
+    pub fn scan<P: AsRef<Path>>(
+        gfa_path: P,
+        selection: &Selection,
+    ) -> io::Result<Self> {
+        info!("Scanning GFA {}", gfa_path.as_ref().display());
+        let mut filter = Self::default();
+
+        match selection {
+            Selection::Paths(paths) => {
+                for line in byte_lines(&gfa_path)? {
+                    let line = line?;
+                    let fields: Vec<&[u8]> = line.split_str("\t").collect();
+                    match fields[0] {
+                        b"P" if fields.len() > 2
+                            && paths.matches(fields[1]) =>
+                        {
+                            filter.segments.extend(
+                                path_line_steps(fields[2])
+                                    .map(|(s, _)| BString::from(s)),
+                            );
+                        }
+                        b"W" if fields.len() > 6
+                            && paths.matches(&walk_name(&fields)) =>
+                        {
+                            filter.segments.extend(
+                                walk_line_steps(fields[6])
+                                    .into_iter()
+                                    .map(|(s, _)| BString::from(s)),
+                            );
+                        }
+                        _ => (),
+                    }
+                }
+
+                filter.paths = Some(paths.clone());
+            }
+            Selection::Segments(names) => {
+                filter.segments =
+                    names.iter().map(|n| BString::from(n.as_slice())).collect();
+
+                // Only the lengths needed to name the path pieces are
+                // recorded, so the paths are read before the segments
+                for line in byte_lines(&gfa_path)? {
+                    let line = line?;
+                    let fields: Vec<&[u8]> = line.split_str("\t").collect();
+                    match fields[0] {
+                        b"P" if fields.len() > 2 => {
+                            let steps: Vec<&[u8]> = path_line_steps(fields[2])
+                                .map(|(s, _)| s)
+                                .collect();
+                            filter.add_needed_lengths(&steps);
+                        }
+                        b"W" if fields.len() > 6 => {
+                            let steps: Vec<&[u8]> = walk_line_steps(fields[6])
+                                .into_iter()
+                                .map(|(s, _)| s)
+                                .collect();
+                            filter.add_needed_lengths(&steps);
+                        }
+                        _ => (),
+                    }
+                }
+
+                for line in byte_lines(&gfa_path)? {
+                    let line = line?;
+                    let fields: Vec<&[u8]> = line.split_str("\t").collect();
+                    if fields[0] == b"S" && fields.len() > 2 {
+                        let len = filter.lengths.get_mut(fields[1].as_bstr());
+                        if let Some(len) = len {
+                            *len = segment_line_length(&fields);
+                        }
+                    }
+                }
+            }
+        }
+
+        debug!("Keeping {} segments", filter.segments.len());
+
+        Ok(filter)
+    }
+
+    /// Make room for the lengths needed to name the pieces of a path
+    /// or walk with these steps, which are those of the segments up
+    /// to its last kept step.
+    fn add_needed_lengths(&mut self, steps: &[&[u8]]) {
+        let last = steps
+            .iter()
+            .rposition(|s| self.segments.contains(s.as_bstr()));
+        if let Some(last) = last {
+            for &seg in steps[..=last].iter() {
+                if !self.lengths.contains_key(seg.as_bstr()) {
+                    self.lengths.insert(seg.into(), 0);
+                }
+            }
+        }
+    }
+
+    /// Read the GFA once more, writing the lines that are part of
+    /// the subgraph to `out` as they are read. Comments and unknown
+    /// record types are passed through unchanged. Returns the number
+    /// of lines written.
+    pub fn write_subgraph<P: AsRef<Path>, W: Write>(
+        &self,
+        gfa_path: P,
+        out: &mut W,
+    ) -> io::Result<usize> {
+        let mut written = 0;
+
+        for line in byte_lines(&gfa_path)? {
+            let line = line?;
+            let fields: Vec<&[u8]> = line.split_str("\t").collect();
+            let has_segment = |ix: usize| {
+                fields
+                    .get(ix)
+                    .is_some_and(|s| self.segments.contains(s.as_bstr()))
+            };
+
+            match fields[0] {
+                b"H" => {
+                    out.write_all(&line)?;
+                    out.write_all(b"\n")?;
+                    written += 1;
+                }
+                b"S" if has_segment(1) => {
+                    out.write_all(&line)?;
+                    out.write_all(b"\n")?;
+                    written += 1;
+                }
+                b"L" | b"C" if has_segment(1) && has_segment(3) => {
+                    out.write_all(&line)?;
+                    out.write_all(b"\n")?;
+                    written += 1;
+                }
+                b"P" if fields.len() > 2 => {
+                    written += self.write_path_line(&line, &fields, out)?;
+                }
+                b"W" if fields.len() > 6 => {
+                    written += self.write_walk_line(&line, &fields, out)?;
+                }
+                b"S" | b"L" | b"C" | b"P" | b"W" => (),
+                _ => {
+                    out.write_all(&line)?;
+                    out.write_all(b"\n")?;
+                    written += 1;
+                }
+            }
+        }
+
+        Ok(written)
+    }
+
+    /// The maximal ranges of consecutive steps that only visit kept
+    /// segments, along with the offsets of the steps.
+    fn pieces<'a, I>(&self, steps: I) -> (Vec<(usize, usize)>, Vec<usize>)
+    where
+        I: Iterator<Item = &'a [u8]>,
+    {
+        let mut pieces = Vec::new();
+        let mut offsets = vec![0];
+        let mut start = None;
+        let mut count = 0;
+
+        for (ix, seg) in steps.enumerate() {
+            let len = self.lengths.get(seg.as_bstr()).copied().unwrap_or(0);
+            offsets.push(offsets[ix] + len);
+
+            let inside = self.segments.contains(seg.as_bstr());
+            match (inside, start) {
+                (true, None) => start = Some(ix),
+                (false, Some(s)) => {
+                    pieces.push((s, ix));
+                    start = None;
+                }
+                _ => (),
+            }
+            count = ix + 1;
+        }
+
+        if let Some(s) = start {
+            pieces.push((s, count));
+        }
+
+        (pieces, offsets)
+    }
+
+    fn write_path_line<W: Write>(
+        &self,
+        line: &[u8],
+        fields: &[&[u8]],
+        out: &mut W,
+    ) -> io::Result<usize> {
+        if let Some(paths) = &self.paths {
+            if !paths.matches(fields[1]) {
+                return Ok(0);
+            }
+            out.write_all(line)?;
+            out.write_all(b"\n")?;
+            return Ok(1);
+        }
+
+        let steps: Vec<&[u8]> = fields[2].split_str(",").collect();
+        let (pieces, offsets) =
+            self.pieces(path_line_steps(fields[2]).map(|(s, _)| s));
+
+        if pieces.len() == 1 && pieces[0] == (0, steps.len()) {
+            out.write_all(line)?;
+            out.write_all(b"\n")?;
+            return Ok(1);
+        }
+
+        let overlaps: Option<Vec<&[u8]>> = fields
+            .get(3)
+            .map(|o| o.split_str(",").collect::<Vec<_>>())
+            .filter(|o| o.len() + 1 == steps.len());
+
+        for &(start, end) in pieces.iter() {
+            write!(
+                out,
+                "P\t{}:{}-{}\t",
+                fields[1].as_bstr(),
+                offsets[start],
+                offsets[end]
+            )?;
+            out.write_all(&steps[start..end].join(&b","[..]))?;
+            out.write_all(b"\t")?;
+            match &overlaps {
+                Some(overlaps) if end - start > 1 => {
+                    out.write_all(&overlaps[start..end - 1].join(&b","[..]))?
+                }
+                _ => out.write_all(b"*")?,
+            }
+            for opt in fields.iter().skip(4) {
+                out.write_all(b"\t")?;
+                out.write_all(opt)?;
+            }
+            out.write_all(b"\n")?;
+        }
+
+        Ok(pieces.len())
+    }
+
+    fn write_walk_line<W: Write>(
+        &self,
+        line: &[u8],
+        fields: &[&[u8]],
+        out: &mut W,
+    ) -> io::Result<usize> {
+        if let Some(paths) = &self.paths {
+            if !paths.matches(&walk_name(fields)) {
+                return Ok(0);
+            }
+            out.write_all(line)?;
+            out.write_all(b"\n")?;
+            return Ok(1);
+        }
+
+        let steps = walk_line_steps(fields[6]);
+        let (pieces, offsets) = self.pieces(steps.iter().map(|(s, _)| *s));
+
+        if pieces.len() == 1 && pieces[0] == (0, steps.len()) {
+            out.write_all(line)?;
+            out.write_all(b"\n")?;
+            return Ok(1);
+        }
+
+        let seq_start: Option<usize> =
+            fields[4].to_str().ok().and_then(|s| s.parse().ok());
+
+        for &(start, end) in pieces.iter() {
+            for field in fields[..4].iter() {
+                out.write_all(field)?;
+                out.write_all(b"\t")?;
+            }
+            match seq_start {
+                Some(seq_start) => write!(
+                    out,
+                    "{}\t{}\t",
+                    seq_start + offsets[start],
+                    seq_start + offsets[end]
+                )?,
+                None => out.write_all(b"*\t*\t")?,
+            }
+            for (seg, orient) in steps[start..end].iter() {
+                out.write_all(&[*orient])?;
+                out.write_all(seg)?;
+            }
+            for opt in fields.iter().skip(7) {
+                out.write_all(b"\t")?;
+                out.write_all(opt)?;
+            }
+            out.write_all(b"\n")?;
+        }
+
+        Ok(pieces.len())
+    }
+}
+
+/// The length of the segment on an S-line, using the LN tag if the
+/// sequence is missing.
+fn segment_line_length(fields: &[&[u8]]) -> usize {
+    if fields[2] != b"*" {
+        return fields[2].len();
+    }
+    fields
+        .iter()
+        .skip(3)
+        .find_map(|f| f.strip_prefix(b"LN:i:"))
+        .and_then(|len| len.to_str().ok()?.parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walk_steps() {
+        let steps = walk_line_steps(b">s1<s22>s3");
+        assert_eq!(
+            steps,
+            vec![(&b"s1"[..], b'>'), (&b"s22"[..], b'<'), (&b"s3"[..], b'>')]
+        );
+    }
+
+    #[test]
+    fn trim_path_line() {
+        let segments: HashSet<BString> =
+            ["2", "3"].iter().map(|&s| BString::from(s)).collect();
+        let lengths: HashMap<BString, usize> = [("1", 4), ("2", 1), ("3", 3)]
+            .iter()
+            .map(|&(s, l)| (BString::from(s), l))
+            .collect();
+        let filter = LineFilter::from_segments(segments, lengths);
+
+        let line = b"P\tx\t1+,2-,3+,1-\t1M,0M,2M\tXY:i:1";
+        let fields: Vec<&[u8]> = line.split_str("\t").collect();
+        let mut out = Vec::new();
+        filter.write_path_line(line, &fields, &mut out).unwrap();
+
+        assert_eq!(out.as_bstr(), "P\tx:4-8\t2-,3+\t0M\tXY:i:1\n");
+    }
+
+    #[test]
+    fn lengths_up_to_last_kept_step() {
+        let segments: HashSet<BString> =
+            ["2", "3"].iter().map(|&s| BString::from(s)).collect();
+        let mut filter = LineFilter::from_segments(segments, HashMap::new());
+
+        filter.add_needed_lengths(&[&b"1"[..], b"3", b"4", b"2", b"5"]);
+        filter.add_needed_lengths(&[&b"6"[..], b"7"]);
+
+        let mut needed: Vec<_> = filter.lengths.keys().cloned().collect();
+        needed.sort();
+        assert_eq!(needed, vec!["1", "2", "3", "4"]);
+    }
+}
@@ -1,3 +1,6 @@
+use std::{fmt, io};
+
+use gfa::{gfa::GFA, optfields::OptFields, writer::write_gfa};
 use indicatif::{ProgressBar, ProgressStyle};
 
 pub(crate) fn progress_bar(len: usize, steady: bool) -> ProgressBar {
@@ -12,3 +15,115 @@ pub(crate) fn progress_bar(len: usize, steady: bool) -> ProgressBar {
     }
     p_bar
 }
+
+/// Split a path name using the PanSN naming scheme,
+/// `sample#haplotype#contig`, into its sample and haplotype. Names
+/// with only two parts, `sample#contig`, have no haplotype. Returns
+/// `None` if the name isn't a PanSN name.
+pub fn pansn_sample_haplotype(name: &[u8]) -> Option<(&[u8], Option<&[u8]>)> {
+    let mut parts = name.splitn(3, |&b| b == b'#');
+    let sample = parts.next()?;
+    let second = parts.next()?;
+    match parts.next() {
+        Some(_) => Some((sample, Some(second))),
+        None => Some((sample, None)),
+    }
+}
+
+/// The reverse complement of a DNA sequence. IUPAC ambiguity codes
+/// are complemented, and the case of each base is kept. Unknown
+/// characters are left as they are.
+pub fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+    fn complement(base: u8) -> u8 {
+        let comp = match base.to_ascii_uppercase() {
+            b'A' => b'T',
+            b'C' => b'G',
+            b'G' => b'C',
+            b'T' | b'U' => b'A',
+            b'R' => b'Y',
+            b'Y' => b'R',
+            b'K' => b'M',
+            b'M' => b'K',
+            b'B' => b'V',
+            b'V' => b'B',
+            b'D' => b'H',
+            b'H' => b'D',
+            other => other,
+        };
+        if base.is_ascii_lowercase() {
+            comp.to_ascii_lowercase()
+        } else {
+            comp
+        }
+    }
+
+    seq.iter().rev().copied().map(complement).collect()
+}
+
+/// A small xorshift64* pseudorandom number generator, so that random
+/// permutations can be reproduced from a seed.
+#[derive(Debug, Clone)]
+pub(crate) struct XorShiftRng(u64);
+
+impl XorShiftRng {
+    pub(crate) fn new(seed: u64) -> Self {
+        // The state must never be zero
+        Self((seed ^ 0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.0 = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Shuffle the slice in place, using Fisher-Yates.
+    pub(crate) fn shuffle<T>(&mut self, items: &mut [T]) {
+        for ix in (1..items.len()).rev() {
+            let other = (self.next_u64() % (ix as u64 + 1)) as usize;
+            items.swap(ix, other);
+        }
+    }
+}
+
+/// Lets the GFA writer, which writes to a `fmt::Write`, write to an
+/// `io::Write`. The writer unwraps the result of every write, so the
+/// first IO error is kept here instead, and the writes after it are
+/// skipped.
+struct IoFmtWriter<W: io::Write> {
+    inner: W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> fmt::Write for IoFmtWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.error.is_none() {
+            if let Err(err) = self.inner.write_all(s.as_bytes()) {
+                self.error = Some(err);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write a GFA straight to `out`, without first building it as a
+/// string.
+pub fn write_gfa_io<N, U, W>(gfa: &GFA<N, U>, out: W) -> io::Result<()>
+where
+    N: fmt::Display,
+    U: OptFields,
+    W: io::Write,
+{
+    let mut writer = IoFmtWriter {
+        inner: out,
+        error: None,
+    };
+    write_gfa(gfa, &mut writer);
+    match writer.error {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
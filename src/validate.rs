@@ -0,0 +1,456 @@
+use bstr::{io::*, BString, ByteSlice};
+use fnv::{FnvHashMap, FnvHashSet};
+
+use std::{fmt, io};
+
+use crate::subgraph::streaming::{path_line_steps, walk_line_steps};
+
+#[allow(unused_imports)]
+use log::{debug, info, warn};
+
+/// The kinds of problems `validate_gfa` looks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IssueKind {
+    /// A line with too few fields, or fields that can't be parsed
+    MalformedLine,
+    /// A segment name used by more than one S-line
+    DuplicateSegment,
+    /// A link, containment, path, or walk using an unknown segment
+    MissingSegment,
+    /// An LN tag that disagrees with the length of the sequence
+    LengthMismatch,
+    /// A sequence with characters other than ACGTN
+    InvalidSequence,
+    /// A path or walk step between segments without a link
+    MissingLink,
+    /// A path overlap that disagrees with the overlap of its link
+    OverlapMismatch,
+}
+
+impl fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            IssueKind::MalformedLine => "malformed-line",
+            IssueKind::DuplicateSegment => "duplicate-segment",
+            IssueKind::MissingSegment => "missing-segment",
+            IssueKind::LengthMismatch => "length-mismatch",
+            IssueKind::InvalidSequence => "invalid-sequence",
+            IssueKind::MissingLink => "missing-link",
+            IssueKind::OverlapMismatch => "overlap-mismatch",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A problem found in a GFA, with the 1-based number of the line it
+/// was found on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub line: usize,
+    pub kind: IssueKind,
+    pub message: String,
+}
+
+/// An oriented segment, with `true` for the reverse orientation.
+type Handle = (BString, bool);
+
+/// A path or walk, kept until all segments and links are known.
+struct PathLine {
+    line: usize,
+    name: BString,
+    steps: Vec<Handle>,
+    overlaps: Option<Vec<BString>>,
+}
+
+fn orientation(orient: u8) -> Option<bool> {
+    match orient {
+        b'+' => Some(false),
+        b'-' => Some(true),
+        _ => None,
+    }
+}
+
+/// The overlap of a link traversed in the other direction: the CIGAR
+/// operations are reversed, and insertions and deletions swap.
+fn flip_overlap(overlap: &[u8]) -> BString {
+    if overlap == b"*" {
+        return overlap.into();
+    }
+    let mut ops = Vec::new();
+    let mut start = 0;
+    for (ix, &b) in overlap.iter().enumerate() {
+        if !b.is_ascii_digit() {
+            let op = match b {
+                b'I' => b'D',
+                b'D' => b'I',
+                other => other,
+            };
+            ops.push((&overlap[start..ix], op));
+            start = ix + 1;
+        }
+    }
+    let mut flipped = BString::from(Vec::with_capacity(overlap.len()));
+    for (len, op) in ops.into_iter().rev() {
+        flipped.extend_from_slice(len);
+        flipped.push(op);
+    }
+    flipped
+}
+
+#[derive(Default)]
+struct Validator {
+    issues: Vec<Issue>,
+    /// The line each segment is defined on
+    segments: FnvHashMap<BString, usize>,
+    /// The overlap of each link, in both directions
+    links: FnvHashMap<(Handle, Handle), BString>,
+    /// Segment references to check once all segments are known
+    references: Vec<(usize, BString)>,
+    paths: Vec<PathLine>,
+}
+
+impl Validator {
+    fn issue(&mut self, line: usize, kind: IssueKind, message: String) {
+        self.issues.push(Issue {
+            line,
+            kind,
+            message,
+        });
+    }
+
+    fn malformed(&mut self, line: usize, record: &[u8]) {
+        self.issue(
+            line,
+            IssueKind::MalformedLine,
+            format!("Malformed {} line", record.as_bstr()),
+        );
+    }
+
+    fn segment_line(&mut self, line: usize, fields: &[&[u8]]) {
+        if fields.len() < 3 {
+            return self.malformed(line, fields[0]);
+        }
+        let name = BString::from(fields[1]);
+        let seq = fields[2];
+
+        if let Some(&first) = self.segments.get(&name) {
+            self.issue(
+                line,
+                IssueKind::DuplicateSegment,
+                format!(
+                    "Segment {} is already defined on line {}",
+                    name, first
+                ),
+            );
+        } else {
+            self.segments.insert(name.clone(), line);
+        }
+
+        let ln_tag =
+            fields.iter().skip(3).find_map(|f| f.strip_prefix(b"LN:i:"));
+        if let Some(tag) = ln_tag {
+            let len: Option<usize> =
+                tag.to_str().ok().and_then(|t| t.parse().ok());
+            match len {
+                None => self.malformed(line, fields[0]),
+                Some(len) if seq != b"*" && len != seq.len() => self.issue(
+                    line,
+                    IssueKind::LengthMismatch,
+                    format!(
+                        "Segment {} has LN:i:{}, but its sequence has length {}",
+                        name,
+                        len,
+                        seq.len()
+                    ),
+                ),
+                _ => (),
+            }
+        }
+
+        if seq != b"*" {
+            let invalid: Vec<u8> = seq
+                .iter()
+                .copied()
+                .filter(|b| !b"ACGTNacgtn".contains(b))
+                .collect();
+            if let Some(&first) = invalid.first() {
+                self.issue(
+                    line,
+                    IssueKind::InvalidSequence,
+                    format!(
+                        "Segment {} has {} characters other than ACGTN, first '{}'",
+                        name,
+                        invalid.len(),
+                        char::from(first).escape_default()
+                    ),
+                );
+            }
+        }
+    }
+
+    fn link_line(&mut self, line: usize, fields: &[&[u8]]) {
+        if fields.len() < 6 {
+            return self.malformed(line, fields[0]);
+        }
+        let (from_rev, to_rev) = match (
+            fields[2].first().copied().and_then(orientation),
+            fields[4].first().copied().and_then(orientation),
+        ) {
+            (Some(f), Some(t)) => (f, t),
+            _ => return self.malformed(line, fields[0]),
+        };
+
+        let from: Handle = (fields[1].into(), from_rev);
+        let to: Handle = (fields[3].into(), to_rev);
+        let overlap = BString::from(fields[5]);
+
+        self.references.push((line, from.0.clone()));
+        self.references.push((line, to.0.clone()));
+
+        let back_from = (to.0.clone(), !to.1);
+        let back_to = (from.0.clone(), !from.1);
+        self.links
+            .insert((back_from, back_to), flip_overlap(&overlap));
+        self.links.insert((from, to), overlap);
+    }
+
+    fn containment_line(&mut self, line: usize, fields: &[&[u8]]) {
+        if fields.len() < 7 {
+            return self.malformed(line, fields[0]);
+        }
+        self.references.push((line, fields[1].into()));
+        self.references.push((line, fields[3].into()));
+    }
+
+    fn path_line(&mut self, line: usize, fields: &[&[u8]]) {
+        if fields.len() < 3 {
+            return self.malformed(line, fields[0]);
+        }
+        let mut steps = Vec::new();
+        for (name, orient) in path_line_steps(fields[2]) {
+            match orientation(orient) {
+                Some(rev) if !name.is_empty() => steps.push((name.into(), rev)),
+                _ => return self.malformed(line, fields[0]),
+            }
+        }
+
+        let overlaps = match fields.get(3) {
+            None => None,
+            Some(&b"*") => None,
+            Some(overlaps) => {
+                let overlaps: Vec<BString> =
+                    overlaps.split_str(",").map(BString::from).collect();
+                if overlaps.len() + 1 != steps.len() {
+                    self.issue(
+                        line,
+                        IssueKind::OverlapMismatch,
+                        format!(
+                            "Path {} has {} overlaps for {} steps",
+                            fields[1].as_bstr(),
+                            overlaps.len(),
+                            steps.len()
+                        ),
+                    );
+                    None
+                } else {
+                    Some(overlaps)
+                }
+            }
+        };
+
+        self.paths.push(PathLine {
+            line,
+            name: fields[1].into(),
+            steps,
+            overlaps,
+        });
+    }
+
+    fn walk_line(&mut self, line: usize, fields: &[&[u8]]) {
+        if fields.len() < 7
+            || !matches!(fields[6].first(), Some(b'>') | Some(b'<'))
+        {
+            return self.malformed(line, fields[0]);
+        }
+        let mut steps: Vec<Handle> = Vec::new();
+        for (name, orient) in walk_line_steps(fields[6]) {
+            if name.is_empty() {
+                return self.malformed(line, fields[0]);
+            }
+            steps.push((name.into(), orient == b'<'));
+        }
+
+        let name = bstr::join("#", &fields[1..4]).into();
+
+        self.paths.push(PathLine {
+            line,
+            name,
+            steps,
+            overlaps: None,
+        });
+    }
+
+    /// Check the references to segments, and the paths, once all
+    /// lines have been read.
+    fn finish(mut self) -> Vec<Issue> {
+        let references = std::mem::take(&mut self.references);
+        for (line, name) in references {
+            if !self.segments.contains_key(&name) {
+                self.issue(
+                    line,
+                    IssueKind::MissingSegment,
+                    format!("Segment {} does not exist", name),
+                );
+            }
+        }
+
+        let paths = std::mem::take(&mut self.paths);
+        for path in paths {
+            self.check_path(&path);
+        }
+
+        self.issues.sort_by_key(|i| i.line);
+        self.issues
+    }
+
+    fn check_path(&mut self, path: &PathLine) {
+        let mut missing: FnvHashSet<&BString> = FnvHashSet::default();
+        for (name, _) in path.steps.iter() {
+            if !self.segments.contains_key(name) && missing.insert(name) {
+                self.issue(
+                    path.line,
+                    IssueKind::MissingSegment,
+                    format!(
+                        "Path {} uses segment {}, which does not exist",
+                        path.name, name
+                    ),
+                );
+            }
+        }
+
+        for (ix, pair) in path.steps.windows(2).enumerate() {
+            let (from, to) = (&pair[0], &pair[1]);
+            // Steps on missing segments have already been reported
+            if missing.contains(&from.0) || missing.contains(&to.0) {
+                continue;
+            }
+            let key = (from.clone(), to.clone());
+            let sign = |rev: bool| if rev { '-' } else { '+' };
+
+            match self.links.get(&key) {
+                None => self.issue(
+                    path.line,
+                    IssueKind::MissingLink,
+                    format!(
+                        "Path {} steps from {}{} to {}{} without a link",
+                        path.name,
+                        from.0,
+                        sign(from.1),
+                        to.0,
+                        sign(to.1)
+                    ),
+                ),
+                Some(link_overlap) => {
+                    let path_overlap = path.overlaps.as_ref().map(|o| &o[ix]);
+                    match path_overlap {
+                        Some(overlap)
+                            if overlap != "*"
+                                && link_overlap != "*"
+                                && overlap != link_overlap =>
+                        {
+                            let message = format!(
+                                "Path {} has overlap {} from {}{} to {}{}, the link has {}",
+                                path.name,
+                                overlap,
+                                from.0,
+                                sign(from.1),
+                                to.0,
+                                sign(to.1),
+                                link_overlap
+                            );
+                            self.issue(
+                                path.line,
+                                IssueKind::OverlapMismatch,
+                                message,
+                            );
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Check a GFA for problems, line by line, without requiring it to be
+/// parseable: segments defined more than once, LN tags disagreeing
+/// with the sequence, sequences with characters other than ACGTN,
+/// links, containments, paths, and walks using unknown segments, path
+/// and walk steps without a corresponding link, and path overlaps
+/// disagreeing with the links. The issues are returned in line
+/// order.
+pub fn validate_gfa<R: io::BufRead>(reader: R) -> io::Result<Vec<Issue>> {
+    let mut validator = Validator::default();
+
+    for (ix, line) in reader.byte_lines().enumerate() {
+        let line = line?;
+        let line_num = ix + 1;
+        let fields: Vec<&[u8]> = line.split_str("\t").collect();
+
+        match fields[0] {
+            b"S" => validator.segment_line(line_num, &fields),
+            b"L" => validator.link_line(line_num, &fields),
+            b"C" => validator.containment_line(line_num, &fields),
+            b"P" => validator.path_line(line_num, &fields),
+            b"W" => validator.walk_line(line_num, &fields),
+            _ => (),
+        }
+    }
+
+    Ok(validator.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flipped_overlaps() {
+        assert_eq!(flip_overlap(b"4M"), "4M");
+        assert_eq!(flip_overlap(b"2M1I3M"), "3M1D2M");
+        assert_eq!(flip_overlap(b"*"), "*");
+    }
+
+    #[test]
+    fn validate_lines() {
+        let gfa = b"H\tVN:Z:1.0
+S\t1\tACGT\tLN:i:4
+S\t2\tAC-T\tLN:i:5
+S\t1\tGG
+L\t1\t+\t2\t+\t1M
+L\t2\t+\t3\t-\t0M
+P\tx\t1+,2+\t1M
+P\ty\t2-,1-,2+\t2M,0M
+W\ts\t1\tc\t0\t8\t>1>2
+P\tz\t1+,4+,2+\t*
+W\ts\t2\tc\t0\t8\t>1>>2
+";
+        let issues = validate_gfa(&gfa[..]).unwrap();
+        let found: Vec<(usize, IssueKind)> =
+            issues.iter().map(|i| (i.line, i.kind)).collect();
+
+        assert_eq!(
+            found,
+            vec![
+                (3, IssueKind::LengthMismatch),
+                (3, IssueKind::InvalidSequence),
+                (4, IssueKind::DuplicateSegment),
+                (6, IssueKind::MissingSegment),
+                (8, IssueKind::OverlapMismatch),
+                (8, IssueKind::MissingLink),
+                (10, IssueKind::MissingSegment),
+                (11, IssueKind::MalformedLine),
+            ]
+        );
+    }
+}
@@ -9,7 +9,10 @@ use rayon::prelude::*;
 
 use gfa::gfa::{Orientation, GFA};
 
-use crate::util::progress_bar;
+use crate::{
+    error::{Error, Result},
+    util::progress_bar,
+};
 
 #[allow(unused_imports)]
 use log::{debug, info, trace, warn};
@@ -23,7 +26,10 @@ pub struct PathData {
     pub paths: Vec<Vec<PathStep>>,
 }
 
-pub fn gfa_path_data(mut gfa: GFA<usize, ()>) -> PathData {
+/// Extract the paths of the graph along with the offset of each step,
+/// and the sequences of the segments. Fails if a path uses a segment
+/// that isn't in the graph.
+pub fn gfa_path_data(mut gfa: GFA<usize, ()>) -> Result<PathData> {
     let segments = std::mem::take(&mut gfa.segments);
 
     info!("Building map from segment IDs to sequences");
@@ -37,23 +43,63 @@ pub fn gfa_path_data(mut gfa: GFA<usize, ()>) -> PathData {
     let p_bar = progress_bar(gfa_paths.len(), false);
 
     info!("Extracting paths and offsets from GFA");
-    let (path_names, paths): (Vec<_>, Vec<_>) = gfa_paths
+    let named_paths = gfa_paths
         .into_par_iter()
         .progress_with(p_bar)
         .map(|mut path| {
-            let steps: Vec<(usize, usize, Orientation)> = path
-                .iter()
-                .scan(1, |offset, (step, orient)| {
-                    let step_offset = *offset;
-                    let step_len = segment_map.get(&step).unwrap().len();
-                    *offset += step_len;
-                    Some((step, step_offset, orient))
-                })
-                .collect();
+            let mut offset = 1;
+            let mut steps: Vec<PathStep> = Vec::new();
+            for (step, orient) in path.iter() {
+                let segment = segment_map.get(&step).ok_or_else(|| {
+                    Error::InvalidGraph(format!(
+                        "Path {} uses segment {}, which is not in the graph",
+                        path.path_name, step
+                    ))
+                })?;
+                steps.push((step, offset, orient));
+                offset += segment.len();
+            }
 
             let path_name = std::mem::take(&mut path.path_name);
 
-            (path_name, steps)
+            Ok((path_name, steps))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let (path_names, paths) = named_paths.into_iter().unzip();
+
+    Ok(PathData {
+        segment_map,
+        path_names,
+        paths,
+    })
+}
+
+/// Build path data from segment sequences and the oriented steps of
+/// each path, with the step offsets computed like in `gfa_path_data`.
+#[cfg(test)]
+pub(crate) fn path_data_from_steps(
+    segments: &[(usize, &str)],
+    paths: &[(&str, &[(usize, Orientation)])],
+) -> PathData {
+    let segment_map: FnvHashMap<usize, BString> = segments
+        .iter()
+        .map(|&(id, seq)| (id, BString::from(seq)))
+        .collect();
+
+    let (path_names, paths) = paths
+        .iter()
+        .map(|&(name, steps)| {
+            let mut offset = 1;
+            let steps = steps
+                .iter()
+                .map(|&(step, orient)| {
+                    let step_offset = offset;
+                    offset += segment_map.get(&step).map_or(0, |s| s.len());
+                    (step, step_offset, orient)
+                })
+                .collect();
+            (BString::from(name), steps)
         })
         .unzip();
 
@@ -670,9 +716,9 @@ impl Default for VariantConfig {
 
 pub type PathIndices = FnvHashMap<u64, FnvHashMap<usize, usize>>;
 
-fn path_data_sub_paths<'a, 'b>(
+pub(crate) fn path_data_sub_paths<'a>(
     path_data: &'a PathData,
-    path_indices: &'b PathIndices,
+    path_indices: &PathIndices,
     from: u64,
     to: u64,
 ) -> Option<Vec<(usize, &'a [PathStep])>> {
@@ -64,12 +64,23 @@ impl Display for VCFRecord {
 
 pub struct VCFHeader {
     reference: PathBuf,
+    sample: Option<BString>,
 }
 
 impl VCFHeader {
     pub fn new<T: AsRef<Path>>(path: T) -> Self {
         let reference = path.as_ref().to_owned();
-        Self { reference }
+        Self {
+            reference,
+            sample: None,
+        }
+    }
+
+    /// Add a sample column, along with the GT, AD, DP, and GQ format
+    /// fields.
+    pub fn with_sample<T: Into<BString>>(mut self, sample: T) -> Self {
+        self.sample = Some(sample.into());
+        self
     }
 }
 
@@ -86,23 +97,40 @@ impl Display for VCFHeader {
             r#"##INFO=<ID=TYPE,Number=A,Type=String,Description="Type of each allele (snv, ins, del, mnp, complex)">"#
         )?;
 
-        // writeln!(
-        //     f,
-        //     r#"##FORMAT=<ID=GT,Number=1,Type=String,Description="Genotype">"#
-        // )?;
+        if self.sample.is_some() {
+            writeln!(
+                f,
+                r#"##FORMAT=<ID=GT,Number=1,Type=String,Description="Genotype">"#
+            )?;
+            writeln!(
+                f,
+                r#"##FORMAT=<ID=AD,Number=R,Type=Integer,Description="Number of reads supporting each allele">"#
+            )?;
+            writeln!(
+                f,
+                r#"##FORMAT=<ID=DP,Number=1,Type=Integer,Description="Number of reads supporting any allele">"#
+            )?;
+            writeln!(
+                f,
+                r#"##FORMAT=<ID=GQ,Number=1,Type=Integer,Description="Genotype quality">"#
+            )?;
+        }
 
         let header_line: BString = bstr::join(
             "\t",
             [
-                "#CHROM", "POS", "ID", "REF", "ALT", "QUAL", "FILTER",
-                "INFO",
-                // "FORMAT",
-                // "SampleName",
+                "#CHROM", "POS", "ID", "REF", "ALT", "QUAL", "FILTER", "INFO",
             ]
             .iter(),
         )
         .into();
 
-        write!(f, "{}", header_line)
+        write!(f, "{}", header_line)?;
+
+        if let Some(sample) = &self.sample {
+            write!(f, "\tFORMAT\t{}", sample)?;
+        }
+
+        Ok(())
     }
 }
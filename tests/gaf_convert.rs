@@ -20,7 +20,7 @@ fn load_pafs(gfa_path: &str, gaf_path: &str) -> Vec<PAF> {
         parser.parse_file(gfa_path).unwrap();
 
     let gaf_path = PathBuf::from(gaf_path);
-    let pafs = gaf_to_paf(gfa, &gaf_path);
+    let pafs = gaf_to_paf(gfa, &gaf_path).unwrap();
 
     pafs
 }